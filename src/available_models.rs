@@ -1,8 +1,117 @@
-pub fn list_available_models() -> Vec<&'static str> {
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// A Gemini model as reported by the models endpoint
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ModelInfo {
+    /// Model id without the `models/` prefix
+    pub name: String,
+    pub display_name: String,
+    pub input_token_limit: Option<u64>,
+    pub output_token_limit: Option<u64>,
+    pub supported_generation_methods: Vec<String>,
+}
+
+impl ModelInfo {
+    fn known(name: &str, display_name: &str, input_token_limit: u64) -> Self {
+        Self {
+            name: name.to_string(),
+            display_name: display_name.to_string(),
+            input_token_limit: Some(input_token_limit),
+            output_token_limit: None,
+            supported_generation_methods: vec![
+                "generateContent".to_string(),
+                "streamGenerateContent".to_string(),
+            ],
+        }
+    }
+
+    pub fn supports(&self, method: &str) -> bool {
+        self.supported_generation_methods
+            .iter()
+            .any(|supported| supported == method)
+    }
+
+    /// The endpoint doesn't report JSON schema support, but every Gemini model
+    /// since 1.5 has it
+    pub fn structured_output(&self) -> bool {
+        self.name.starts_with("gemini-") && !self.name.starts_with("gemini-1.0")
+    }
+
+    /// Short capability summary, e.g. "1M context, streaming, JSON output"
+    pub fn capabilities(&self) -> String {
+        let mut capabilities = Vec::new();
+        if let Some(limit) = self.input_token_limit {
+            capabilities.push(if limit >= 1_000_000 {
+                format!("{}M context", limit / 1_000_000)
+            } else {
+                format!("{}K context", limit / 1_000)
+            });
+        }
+        if self.supports("streamGenerateContent") {
+            capabilities.push("streaming".to_string());
+        }
+        if self.structured_output() {
+            capabilities.push("JSON output".to_string());
+        }
+        capabilities.join(", ")
+    }
+}
+
+/// Models offered when the models endpoint can't be reached and nothing is cached
+pub fn static_models() -> Vec<ModelInfo> {
     vec![
-        "gemini-2.0-flash",
-        "gemini-2.0-pro",
-        "gemini-1.5-flash",
-        "gemini-1.5-pro",
+        ModelInfo::known("gemini-2.5-flash", "Gemini 2.5 Flash", 1_048_576),
+        ModelInfo::known("gemini-2.5-pro", "Gemini 2.5 Pro", 1_048_576),
+        ModelInfo::known("gemini-2.0-flash", "Gemini 2.0 Flash", 1_048_576),
+        ModelInfo::known("gemini-2.0-flash-lite", "Gemini 2.0 Flash-Lite", 1_048_576),
     ]
 }
+
+#[derive(Serialize, Deserialize)]
+struct CachedModels {
+    fetched_at: u64,
+    models: Vec<ModelInfo>,
+}
+
+/// Last model list fetched from the models endpoint
+pub struct ModelCache {
+    path: PathBuf,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+impl ModelCache {
+    pub fn open(config_dir: &Path) -> Self {
+        Self {
+            path: config_dir.join("nexsh_models.json"),
+        }
+    }
+
+    /// Cached models fetched at most `max_age_secs` ago
+    pub fn load(&self, max_age_secs: u64) -> Option<Vec<ModelInfo>> {
+        let cached: CachedModels =
+            serde_json::from_str(&fs::read_to_string(&self.path).ok()?).ok()?;
+        (now().saturating_sub(cached.fetched_at) <= max_age_secs).then_some(cached.models)
+    }
+
+    pub fn save(&self, models: &[ModelInfo]) -> Result<(), Box<dyn Error>> {
+        let cached = CachedModels {
+            fetched_at: now(),
+            models: models.to_vec(),
+        };
+        fs::write(&self.path, serde_json::to_string_pretty(&cached)?)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+use crate::types::GeminiResponse;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    created_at: u64,
+    request: String,
+    response: GeminiResponse,
+}
+
+/// Responses to previous requests, stored as a single JSON file keyed by
+/// [`cache_key`]
+pub struct ResponseCache {
+    path: PathBuf,
+    ttl_secs: u64,
+    max_entries: usize,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+impl ResponseCache {
+    pub fn open(config_dir: &Path, ttl_secs: u64, max_entries: usize) -> Self {
+        Self {
+            path: config_dir.join("nexsh_cache.json"),
+            ttl_secs,
+            max_entries,
+        }
+    }
+
+    /// Unreadable cache files are treated as empty rather than failing the request
+    fn load(&self) -> HashMap<String, CacheEntry> {
+        fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Cached response for `key`, if present and younger than the TTL
+    pub fn get(&self, key: &str) -> Option<GeminiResponse> {
+        let entry = self.load().remove(key)?;
+        (now().saturating_sub(entry.created_at) < self.ttl_secs).then_some(entry.response)
+    }
+
+    /// Store a response, dropping expired entries and then the oldest ones to
+    /// stay within `max_entries`
+    pub fn put(
+        &self,
+        key: &str,
+        request: &str,
+        response: &GeminiResponse,
+    ) -> Result<(), Box<dyn Error>> {
+        let now = now();
+        let mut entries = self.load();
+        entries.retain(|_, entry| now.saturating_sub(entry.created_at) < self.ttl_secs);
+        entries.remove(key);
+        if entries.len() >= self.max_entries {
+            let mut ages: Vec<(u64, String)> = entries
+                .iter()
+                .map(|(key, entry)| (entry.created_at, key.clone()))
+                .collect();
+            ages.sort();
+            for (_, key) in ages
+                .iter()
+                .take(entries.len() + 1 - self.max_entries.max(1))
+            {
+                entries.remove(key);
+            }
+        }
+        entries.insert(
+            key.to_string(),
+            CacheEntry {
+                created_at: now,
+                request: request.to_string(),
+                response: response.clone(),
+            },
+        );
+        fs::write(&self.path, serde_json::to_string(&entries)?)?;
+        Ok(())
+    }
+}
+
+/// Lowercase the request, collapse whitespace and drop trailing punctuation so
+/// trivially different phrasings share a cache entry
+pub fn normalize(request: &str) -> String {
+    request
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+        .trim_end_matches(['.', '!', '?'])
+        .to_string()
+}
+
+/// 64-bit FNV-1a, stable across runs and Rust versions unlike `DefaultHasher`
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Key for a request made in a given context; `context` should only hold
+/// details that change what command is appropriate (OS, shell, cwd, tools, ...)
+pub fn cache_key(request: &str, context: &str) -> String {
+    format!(
+        "{:016x}{:016x}",
+        fnv1a(normalize(request).as_bytes()),
+        fnv1a(context.as_bytes())
+    )
+}
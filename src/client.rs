@@ -0,0 +1,444 @@
+use crate::available_models::ModelInfo;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::Value;
+use std::{fmt, time::Duration};
+
+const API_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
+
+/// Finish and block reasons Gemini reports when its safety filters stop a response
+const SAFETY_REASONS: &[&str] = &[
+    "SAFETY",
+    "BLOCKLIST",
+    "PROHIBITED_CONTENT",
+    "SPII",
+    "RECITATION",
+];
+
+/// Why a Gemini request failed, each with a message telling the user what to do
+#[derive(Debug)]
+pub enum ApiError {
+    /// Missing, invalid or unauthorized API key
+    Auth(String),
+    /// Too many requests or quota exhausted
+    RateLimit(String),
+    /// Connection failures and server-side errors
+    Network(String),
+    /// The prompt or the response was blocked by the safety filters
+    Safety(String),
+    /// The response couldn't be decoded
+    Malformed(String),
+    /// No candidate had any content; holds the finish reason when one was given
+    Empty(Option<String>),
+    /// No response within the configured timeout (seconds)
+    Timeout(u64),
+    /// Any other error reported by the API
+    Api { status: u16, message: String },
+}
+
+impl ApiError {
+    /// Transient failures worth retrying
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            ApiError::RateLimit(_) | ApiError::Network(_) | ApiError::Timeout(_)
+        )
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Auth(message) => write!(
+                f,
+                "Gemini rejected the API key ({}). Run 'init' to set a valid key.",
+                message
+            ),
+            ApiError::RateLimit(message) => write!(
+                f,
+                "Gemini rate limit or quota exceeded ({}). Wait a moment or check your quota in Google AI Studio.",
+                message
+            ),
+            ApiError::Network(message) => write!(
+                f,
+                "Could not reach Gemini ({}). Check your internet connection or proxy settings.",
+                message
+            ),
+            ApiError::Safety(reason) => write!(
+                f,
+                "Gemini's safety filters blocked this request ({}). Try rephrasing it.",
+                reason
+            ),
+            ApiError::Empty(reason) => write!(
+                f,
+                "Gemini returned an empty response (finish reason: {}). Try again or rephrase the request.",
+                reason.as_deref().unwrap_or("none")
+            ),
+            ApiError::Malformed(message) => {
+                write!(f, "Unexpected response from Gemini: {}", message)
+            }
+            ApiError::Timeout(secs) => write!(
+                f,
+                "No response from Gemini within {}s. Try again or raise request_timeout_secs in the config.",
+                secs
+            ),
+            ApiError::Api { status, message } => {
+                write!(f, "Gemini API error {}: {}", status, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GenerateContentResponse {
+    pub candidates: Vec<Candidate>,
+    pub prompt_feedback: Option<PromptFeedback>,
+    pub usage_metadata: Option<UsageMetadata>,
+}
+
+/// Tokens billed for a request
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct UsageMetadata {
+    pub prompt_token_count: u64,
+    pub candidates_token_count: u64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Candidate {
+    pub content: Option<Content>,
+    pub finish_reason: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Content {
+    pub parts: Vec<Part>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Part {
+    pub text: Option<String>,
+    pub function_call: Option<FunctionCall>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct FunctionCall {
+    pub name: String,
+    pub args: Value,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PromptFeedback {
+    pub block_reason: Option<String>,
+}
+
+impl GenerateContentResponse {
+    /// The candidate a reply is taken from: the first one with any content.
+    /// Only one candidate is ever used, so a reply never runs several commands
+    pub fn candidate(&self) -> Option<&Candidate> {
+        self.candidates.iter().find(|c| !c.parts().is_empty())
+    }
+}
+
+impl Candidate {
+    pub fn parts(&self) -> &[Part] {
+        self.content
+            .as_ref()
+            .map(|content| content.parts.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// All text parts joined, since a single reply may be split across parts
+    pub fn text(&self) -> String {
+        self.parts()
+            .iter()
+            .filter_map(|part| part.text.as_deref())
+            .collect()
+    }
+
+    pub fn function_calls(&self) -> Vec<FunctionCall> {
+        self.parts()
+            .iter()
+            .filter_map(|part| part.function_call.clone())
+            .collect()
+    }
+}
+
+/// Minimal client for the Gemini content generation and model listing endpoints
+pub struct GeminiClient {
+    api_key: String,
+    http: reqwest::Client,
+    timeout_secs: u64,
+}
+
+impl GeminiClient {
+    pub fn new(api_key: String, timeout_secs: u64) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(timeout_secs))
+            .build()
+            .unwrap_or_default();
+        Self {
+            api_key,
+            http,
+            timeout_secs,
+        }
+    }
+
+    /// Send a single `generateContent` request, without retrying
+    pub async fn generate_content(
+        &self,
+        model: &str,
+        request: &Value,
+    ) -> Result<GenerateContentResponse, ApiError> {
+        let response = self
+            .post(&format!("{}:generateContent", model), request)
+            .await?;
+        let body = response
+            .text()
+            .await
+            .map_err(|e| self.classify_transport(e))?;
+        let response: GenerateContentResponse =
+            serde_json::from_str(&body).map_err(|e| ApiError::Malformed(e.to_string()))?;
+        check(response)
+    }
+
+    /// Send a `streamGenerateContent` request, calling `on_text` with each piece
+    /// of reply text as it arrives, and return the chunks merged into one response
+    pub async fn stream_generate_content(
+        &self,
+        model: &str,
+        request: &Value,
+        mut on_text: impl FnMut(&str),
+    ) -> Result<GenerateContentResponse, ApiError> {
+        let mut response = self
+            .post(&format!("{}:streamGenerateContent?alt=sse", model), request)
+            .await?;
+
+        let mut merged = GenerateContentResponse::default();
+        let mut text = String::new();
+        let mut calls = Vec::new();
+        let mut finish_reason = None;
+        let mut buffer: Vec<u8> = Vec::new();
+        loop {
+            let bytes = response
+                .chunk()
+                .await
+                .map_err(|e| self.classify_transport(e))?;
+            let done = bytes.is_none();
+            match bytes {
+                // Events are separated by blank lines, with either LF or CRLF line endings
+                Some(bytes) => buffer.extend(bytes.iter().filter(|&&b| b != b'\r')),
+                // Terminate a last event the server didn't end with a blank line
+                None => buffer.extend_from_slice(b"\n\n"),
+            }
+            while let Some(end) = buffer.windows(2).position(|w| w == b"\n\n") {
+                let event: Vec<u8> = buffer.drain(..end + 2).collect();
+                let data: String = String::from_utf8_lossy(&event)
+                    .lines()
+                    .filter_map(|line| line.strip_prefix("data:"))
+                    .map(str::trim_start)
+                    .collect();
+                if data.is_empty() {
+                    continue;
+                }
+                let chunk: GenerateContentResponse =
+                    serde_json::from_str(&data).map_err(|e| ApiError::Malformed(e.to_string()))?;
+                if chunk.prompt_feedback.is_some() {
+                    merged.prompt_feedback = chunk.prompt_feedback;
+                }
+                // Every chunk reports the running totals, so the last one wins
+                if chunk.usage_metadata.is_some() {
+                    merged.usage_metadata = chunk.usage_metadata;
+                }
+                let Some(candidate) = chunk.candidates.into_iter().next() else {
+                    continue;
+                };
+                finish_reason = candidate.finish_reason.or(finish_reason);
+                for part in candidate.content.map(|c| c.parts).unwrap_or_default() {
+                    if let Some(delta) = part.text {
+                        on_text(&delta);
+                        text.push_str(&delta);
+                    }
+                    if let Some(call) = part.function_call {
+                        calls.push(Part {
+                            text: None,
+                            function_call: Some(call),
+                        });
+                    }
+                }
+            }
+            if done {
+                break;
+            }
+        }
+
+        let mut parts = Vec::new();
+        if !text.is_empty() {
+            parts.push(Part {
+                text: Some(text),
+                function_call: None,
+            });
+        }
+        parts.extend(calls);
+        merged.candidates.push(Candidate {
+            content: Some(Content { parts }),
+            finish_reason,
+        });
+        check(merged)
+    }
+
+    /// List the models that can generate content, following every result page
+    pub async fn list_models(&self) -> Result<Vec<ModelInfo>, ApiError> {
+        #[derive(Default, Deserialize)]
+        #[serde(rename_all = "camelCase", default)]
+        struct ModelPage {
+            models: Vec<ModelInfo>,
+            next_page_token: Option<String>,
+        }
+
+        let mut models = Vec::new();
+        let mut page_token: Option<String> = None;
+        loop {
+            let mut url = format!("{}/models?pageSize=1000", API_URL);
+            if let Some(token) = &page_token {
+                url.push_str(&format!("&pageToken={}", token));
+            }
+            let body = self
+                .send(self.http.get(&url))
+                .await?
+                .text()
+                .await
+                .map_err(|e| self.classify_transport(e))?;
+            let page: ModelPage =
+                serde_json::from_str(&body).map_err(|e| ApiError::Malformed(e.to_string()))?;
+            models.extend(
+                page.models
+                    .into_iter()
+                    .filter(|model| model.supports("generateContent"))
+                    .map(|mut model| {
+                        if let Some(name) = model.name.strip_prefix("models/") {
+                            model.name = name.to_string();
+                        }
+                        model
+                    }),
+            );
+            match page.next_page_token.filter(|token| !token.is_empty()) {
+                Some(token) => page_token = Some(token),
+                None => return Ok(models),
+            }
+        }
+    }
+
+    /// POST `request` to a model method
+    async fn post(&self, method: &str, request: &Value) -> Result<reqwest::Response, ApiError> {
+        let url = format!("{}/models/{}", API_URL, method);
+        self.send(self.http.post(&url).json(request)).await
+    }
+
+    /// Authenticate and send a request, turning HTTP errors into [`ApiError`]s
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response, ApiError> {
+        if self.api_key.is_empty() {
+            return Err(ApiError::Auth("no API key configured".to_string()));
+        }
+        let response = request
+            .header("x-goog-api-key", &self.api_key)
+            .send()
+            .await
+            .map_err(|e| self.classify_transport(e))?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let body = response
+            .text()
+            .await
+            .map_err(|e| self.classify_transport(e))?;
+        Err(classify_status(status, &body))
+    }
+
+    fn classify_transport(&self, error: reqwest::Error) -> ApiError {
+        let error = error.without_url();
+        if error.is_timeout() {
+            ApiError::Timeout(self.timeout_secs)
+        } else if error.is_decode() {
+            ApiError::Malformed(error.to_string())
+        } else {
+            // reqwest's own message is generic, the cause (DNS, TLS, refused) is in the source chain
+            let mut message = error.to_string();
+            let mut source = std::error::Error::source(&error);
+            while let Some(cause) = source {
+                message = cause.to_string();
+                source = cause.source();
+            }
+            ApiError::Network(message)
+        }
+    }
+}
+
+/// Reject responses that were blocked or have no usable candidate
+fn check(response: GenerateContentResponse) -> Result<GenerateContentResponse, ApiError> {
+    if let Some(reason) = response
+        .prompt_feedback
+        .as_ref()
+        .and_then(|feedback| feedback.block_reason.as_ref())
+    {
+        return Err(ApiError::Safety(reason.clone()));
+    }
+    if response.candidate().is_some() {
+        return Ok(response);
+    }
+    // A response whose candidates were all stopped by the filters has nothing usable
+    let blocked = response.candidates.iter().find_map(|candidate| {
+        candidate
+            .finish_reason
+            .as_deref()
+            .filter(|reason| SAFETY_REASONS.contains(reason))
+    });
+    Err(match blocked {
+        Some(reason) => ApiError::Safety(reason.to_string()),
+        None => ApiError::Empty(
+            response
+                .candidates
+                .first()
+                .and_then(|candidate| candidate.finish_reason.clone()),
+        ),
+    })
+}
+
+/// Map an HTTP error status and the API's error body to an [`ApiError`]
+fn classify_status(status: StatusCode, body: &str) -> ApiError {
+    let error = serde_json::from_str::<Value>(body).unwrap_or_default()["error"].clone();
+    let message = error["message"]
+        .as_str()
+        .map(|message| message.to_string())
+        .unwrap_or_else(|| body.trim().to_string());
+    let reason = error["status"].as_str().unwrap_or_default();
+
+    if status == StatusCode::UNAUTHORIZED
+        || status == StatusCode::FORBIDDEN
+        || reason == "UNAUTHENTICATED"
+        || body.contains("API_KEY_INVALID")
+    {
+        ApiError::Auth(message)
+    } else if status == StatusCode::TOO_MANY_REQUESTS || reason == "RESOURCE_EXHAUSTED" {
+        ApiError::RateLimit(message)
+    } else if status == StatusCode::GATEWAY_TIMEOUT {
+        ApiError::Network(format!("gateway timeout: {}", message))
+    } else if status.is_server_error() {
+        ApiError::Network(format!("server error {}: {}", status.as_u16(), message))
+    } else {
+        ApiError::Api {
+            status: status.as_u16(),
+            message,
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use crate::types::Message;
+
+/// Rough per-message overhead for role and formatting tokens
+const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+/// Maximum characters of a single message included in a summarization transcript
+const MAX_TRANSCRIPT_CHARS: usize = 2000;
+
+/// Estimate the token count of a text (about four characters per token)
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+pub fn estimate_message_tokens(messages: &[Message]) -> usize {
+    messages
+        .iter()
+        .map(|m| estimate_tokens(&m.text()) + MESSAGE_OVERHEAD_TOKENS)
+        .sum()
+}
+
+/// Number of oldest messages to fold into a summary so the rest fits in half of
+/// `token_budget` and `max_messages`, or 0 when the context is within both limits.
+/// The newest message is always kept.
+pub fn messages_to_compact(
+    messages: &[Message],
+    token_budget: usize,
+    max_messages: usize,
+) -> usize {
+    if estimate_message_tokens(messages) <= token_budget && messages.len() <= max_messages {
+        return 0;
+    }
+    let mut kept_tokens = 0;
+    let mut kept = 0;
+    for message in messages.iter().rev() {
+        let tokens = estimate_tokens(&message.text()) + MESSAGE_OVERHEAD_TOKENS;
+        if kept > 0 && (kept_tokens + tokens > token_budget / 2 || kept >= max_messages / 2) {
+            break;
+        }
+        kept_tokens += tokens;
+        kept += 1;
+    }
+    messages.len() - kept
+}
+
+/// Render messages as a plain transcript for the summarization prompt
+pub fn transcript(messages: &[Message]) -> String {
+    messages
+        .iter()
+        .map(|m| {
+            let text = m.text();
+            let content: String = text.chars().take(MAX_TRANSCRIPT_CHARS).collect();
+            let ellipsis = if content.len() < text.len() {
+                "…"
+            } else {
+                ""
+            };
+            format!("{}: {}{}", m.gemini_role(), content, ellipsis)
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::MessageKind;
+
+    fn requests(count: usize) -> Vec<Message> {
+        (0..count)
+            .map(|i| {
+                Message::new(MessageKind::UserRequest {
+                    text: format!("request number {}", i),
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn compacts_nothing_within_limits() {
+        assert_eq!(messages_to_compact(&requests(5), 1000, 10), 0);
+    }
+
+    #[test]
+    fn keeps_half_of_the_message_limit() {
+        assert_eq!(messages_to_compact(&requests(10), 1000, 4), 8);
+    }
+
+    #[test]
+    fn always_keeps_the_newest_message() {
+        assert_eq!(messages_to_compact(&requests(10), 1, 100), 9);
+    }
+}
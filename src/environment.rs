@@ -0,0 +1,202 @@
+use crate::tools::{find_in_path, run_capped};
+use std::{env, fs, path::Path};
+
+const MAX_NAME_LEN: usize = 64;
+
+#[cfg(target_os = "linux")]
+const PACKAGE_MANAGERS: &[&str] = &[
+    "apt", "dnf", "yum", "pacman", "zypper", "apk", "emerge", "nix-env", "flatpak", "snap", "brew",
+];
+#[cfg(target_os = "macos")]
+const PACKAGE_MANAGERS: &[&str] = &["brew", "port", "nix-env"];
+#[cfg(target_os = "windows")]
+const PACKAGE_MANAGERS: &[&str] = &["winget", "choco", "scoop"];
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+const PACKAGE_MANAGERS: &[&str] = &["pkg", "pkg_add", "brew"];
+
+/// Snapshot of the local machine, rendered into the system prompt every turn
+#[derive(Debug)]
+pub struct Environment {
+    pub cwd: String,
+    pub shell: Option<String>,
+    pub distro: Option<String>,
+    pub package_managers: Vec<&'static str>,
+    pub tools: Vec<String>,
+    pub git: Option<String>,
+    pub listing: Vec<String>,
+    pub listing_total: usize,
+}
+
+impl Environment {
+    /// Gather the environment, checking `tools` for availability and listing at
+    /// most `max_entries` entries of the current directory
+    pub fn gather(tools: &[String], max_entries: usize) -> Self {
+        let (listing, listing_total) = list_current_dir(max_entries);
+        Self {
+            git: git_summary(),
+            listing,
+            listing_total,
+            ..Self::gather_basic(tools)
+        }
+    }
+
+    /// Gather only what [`Environment::fingerprint`] uses, skipping the git
+    /// state and the directory listing, which are slow in large repositories
+    pub fn gather_basic(tools: &[String]) -> Self {
+        let cwd = env::current_dir()
+            .map(|dir| dir.display().to_string())
+            .unwrap_or_default();
+        Self {
+            cwd,
+            shell: detect_shell(),
+            distro: detect_distro(),
+            package_managers: PACKAGE_MANAGERS
+                .iter()
+                .copied()
+                .filter(|manager| find_in_path(manager).is_some())
+                .collect(),
+            tools: tools
+                .iter()
+                .filter(|tool| find_in_path(tool).is_some())
+                .cloned()
+                .collect(),
+            git: None,
+            listing: Vec::new(),
+            listing_total: 0,
+        }
+    }
+
+    /// Render as prompt context lines
+    pub fn render(&self) -> String {
+        let mut lines = vec![format!("- Current directory: {}", self.cwd)];
+        if cfg!(target_os = "windows") {
+            lines.push("- Commands are executed with: cmd /C".to_string());
+        } else {
+            lines.push("- Commands are executed with: sh -c".to_string());
+        }
+        if let Some(shell) = &self.shell {
+            lines.push(format!("- User shell: {}", shell));
+        }
+        if let Some(distro) = &self.distro {
+            lines.push(format!("- Distribution: {}", distro));
+        }
+        if self.package_managers.is_empty() {
+            lines.push("- Package managers: none found".to_string());
+        } else {
+            lines.push(format!(
+                "- Package managers: {}",
+                self.package_managers.join(", ")
+            ));
+        }
+        if !self.tools.is_empty() {
+            lines.push(format!("- Available tools: {}", self.tools.join(", ")));
+        }
+        if let Some(git) = &self.git {
+            lines.push(format!("- Git: {}", git));
+        }
+        if !self.listing.is_empty() {
+            let shown = if self.listing.len() < self.listing_total {
+                format!(" (first {} of {})", self.listing.len(), self.listing_total)
+            } else {
+                String::new()
+            };
+            lines.push(format!(
+                "- Directory contents{}: {}",
+                shown,
+                self.listing.join(", ")
+            ));
+        }
+        lines.join("\n")
+    }
+
+    /// The parts of the environment that decide which command fits a request,
+    /// leaving out volatile details like git status and directory contents
+    pub fn fingerprint(&self) -> String {
+        format!(
+            "{}|{}|{}|{}|{}|{}",
+            env::consts::OS,
+            self.cwd,
+            self.shell.as_deref().unwrap_or_default(),
+            self.distro.as_deref().unwrap_or_default(),
+            self.package_managers.join(","),
+            self.tools.join(",")
+        )
+    }
+}
+
+fn cap(mut name: String) -> String {
+    if name.chars().count() > MAX_NAME_LEN {
+        name = name.chars().take(MAX_NAME_LEN).collect();
+        name.push('…');
+    }
+    name
+}
+
+fn detect_shell() -> Option<String> {
+    let shell = if cfg!(target_os = "windows") {
+        if env::var_os("PSModulePath").is_some() {
+            Some("powershell".to_string())
+        } else {
+            env::var("ComSpec").ok()
+        }
+    } else {
+        env::var("SHELL").ok()
+    };
+    shell.map(|shell| {
+        Path::new(&shell)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or(shell)
+    })
+}
+
+fn detect_distro() -> Option<String> {
+    if cfg!(target_os = "macos") {
+        let (_, version) = run_capped("sw_vers", &["-productVersion"])?;
+        return Some(format!("macOS {}", version.trim()));
+    }
+    let os_release = fs::read_to_string("/etc/os-release").ok()?;
+    os_release.lines().find_map(|line| {
+        line.strip_prefix("PRETTY_NAME=")
+            .map(|name| name.trim_matches('"').to_string())
+    })
+}
+
+fn git_summary() -> Option<String> {
+    let (is_repo, branch) = run_capped("git", &["rev-parse", "--abbrev-ref", "HEAD"])?;
+    if !is_repo {
+        return None;
+    }
+    let (_, status) = run_capped("git", &["status", "--porcelain"])?;
+    let changed = status.lines().count();
+    Some(format!(
+        "branch {}, {}",
+        branch.trim(),
+        if changed == 0 {
+            "clean working tree".to_string()
+        } else {
+            format!("{} changed files", changed)
+        }
+    ))
+}
+
+fn list_current_dir(max_entries: usize) -> (Vec<String>, usize) {
+    let Ok(entries) = fs::read_dir(".") else {
+        return (Vec::new(), 0);
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| {
+            let name = cap(entry.file_name().to_string_lossy().into_owned());
+            if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                format!("{}/", name)
+            } else {
+                name
+            }
+        })
+        .collect();
+    names.sort();
+    let total = names.len();
+    names.truncate(max_entries);
+    (names, total)
+}
@@ -0,0 +1,267 @@
+use crate::{
+    session,
+    types::{Message, MessageKind, CONTEXT_VERSION},
+};
+use chrono::{DateTime, Local, Utc};
+use clap::ValueEnum;
+use serde_json::json;
+use std::{error::Error, path::Path};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// Readable Markdown transcript
+    #[value(alias = "md")]
+    Markdown,
+    /// Raw messages, importable with `import`
+    Json,
+    /// Shell script replaying the executed commands
+    #[value(name = "sh", alias = "shell")]
+    Shell,
+}
+
+impl ExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "md",
+            ExportFormat::Json => "json",
+            ExportFormat::Shell => "sh",
+        }
+    }
+
+    /// Guess the format from a file extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "md" | "markdown" => Some(ExportFormat::Markdown),
+            "json" => Some(ExportFormat::Json),
+            "sh" => Some(ExportFormat::Shell),
+            _ => None,
+        }
+    }
+}
+
+pub fn render(
+    messages: &[Message],
+    session: &str,
+    format: ExportFormat,
+) -> Result<String, Box<dyn Error>> {
+    match format {
+        ExportFormat::Markdown => Ok(render_markdown(messages, session)),
+        ExportFormat::Json => Ok(serde_json::to_string_pretty(&json!({
+            "version": CONTEXT_VERSION,
+            "session": session,
+            "exported_at": Utc::now().to_rfc3339(),
+            "messages": messages,
+        }))?),
+        ExportFormat::Shell => Ok(render_shell(messages, session)),
+    }
+}
+
+/// Parse an exported transcript back into messages; JSON and shell exports are supported
+pub fn parse(content: &str, format: ExportFormat) -> Result<Vec<Message>, Box<dyn Error>> {
+    match format {
+        ExportFormat::Json => session::parse_messages(content),
+        ExportFormat::Shell => Ok(parse_shell(content)),
+        ExportFormat::Markdown => {
+            Err("Markdown transcripts can't be imported, export as json or sh instead".into())
+        }
+    }
+}
+
+fn format_timestamp(timestamp: u64) -> String {
+    DateTime::from_timestamp(timestamp as i64, 0)
+        .map(|time| {
+            time.with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        })
+        .unwrap_or_default()
+}
+
+fn fenced(lang: &str, text: &str) -> String {
+    // Use a fence longer than any backtick run inside the text
+    let longest = text
+        .split(|c| c != '`')
+        .map(|run| run.len())
+        .max()
+        .unwrap_or(0);
+    let fence = "`".repeat(longest.max(2) + 1);
+    format!("{}{}\n{}\n{}", fence, lang, text.trim_end(), fence)
+}
+
+fn render_markdown(messages: &[Message], session: &str) -> String {
+    let mut out = format!(
+        "# NexSh session: {}\n\n_Exported {}_\n",
+        session,
+        Local::now().format("%Y-%m-%d %H:%M")
+    );
+    for message in messages {
+        let time = format_timestamp(message.timestamp);
+        match &message.kind {
+            MessageKind::UserRequest { text } => {
+                out.push_str(&format!("\n## 🧑 {}\n\n_{}_\n", text.trim(), time));
+            }
+            MessageKind::AssistantResponse { text } => {
+                out.push_str(&format!("\n**🤖 NexSh:** {}\n", text.trim()));
+            }
+            MessageKind::ProposedCommand {
+                command,
+                message,
+                dangerous,
+                category,
+            } => {
+                out.push_str(&format!(
+                    "\n**🤖 NexSh:** {}\n\n_Category: {}{}_\n\n{}\n",
+                    message.trim(),
+                    category,
+                    if *dangerous { ", ⚠️ dangerous" } else { "" },
+                    fenced("sh", command)
+                ));
+            }
+            MessageKind::ExecutionResult {
+                exit_code,
+                stdout,
+                stderr,
+                ..
+            } => {
+                let status = match exit_code {
+                    Some(0) => "✅ Succeeded".to_string(),
+                    Some(code) => format!("❌ Failed with exit code {}", code),
+                    None => "Output".to_string(),
+                };
+                out.push_str(&format!("\n{}\n", status));
+                if !stdout.is_empty() {
+                    out.push_str(&format!("\n{}\n", fenced("", stdout)));
+                }
+                if !stderr.is_empty() {
+                    out.push_str(&format!("\nstderr:\n\n{}\n", fenced("", stderr)));
+                }
+            }
+            MessageKind::SystemNote { text } => {
+                for line in text.trim().lines() {
+                    out.push_str(&format!("\n> {}", line));
+                }
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+fn comment(text: &str) -> String {
+    text.trim()
+        .lines()
+        .map(|line| {
+            if line.is_empty() {
+                "#\n".to_string()
+            } else {
+                format!("# {}\n", line)
+            }
+        })
+        .collect()
+}
+
+fn render_shell(messages: &[Message], session: &str) -> String {
+    let mut out = format!(
+        "#!/bin/sh\n# NexSh session: {}\n# Exported {}\n",
+        session,
+        Local::now().format("%Y-%m-%d %H:%M")
+    );
+    let mut request: Option<&str> = None;
+    for (i, message) in messages.iter().enumerate() {
+        match &message.kind {
+            MessageKind::UserRequest { text } => request = Some(text),
+            MessageKind::ProposedCommand {
+                command, message, ..
+            } => {
+                // Only commands that were actually executed are replayed
+                let executed = messages[i + 1..]
+                    .iter()
+                    .take_while(|m| !matches!(m.kind, MessageKind::ProposedCommand { .. }))
+                    .find_map(|m| match &m.kind {
+                        MessageKind::ExecutionResult {
+                            command: executed,
+                            exit_code,
+                            ..
+                        } if executed == command => Some(exit_code.unwrap_or(-1)),
+                        _ => None,
+                    });
+                let Some(exit_code) = executed else {
+                    continue;
+                };
+
+                out.push('\n');
+                if let Some(request) = request.take() {
+                    out.push_str(&comment(&format!("Request: {}", request)));
+                }
+                out.push_str(&comment(message));
+                if exit_code == 0 {
+                    out.push_str(command);
+                    out.push('\n');
+                } else {
+                    out.push_str(&format!("# Failed with exit code {}:\n", exit_code));
+                    out.push_str(&comment(command));
+                }
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+/// Rebuild messages from an exported shell script: comments become the model's
+/// explanations and each command a proposed command
+fn parse_shell(content: &str) -> Vec<Message> {
+    let mut messages = Vec::new();
+    let mut notes: Vec<&str> = Vec::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with("#!") || line.is_empty() {
+            continue;
+        }
+        if let Some(comment) = line.strip_prefix('#') {
+            let comment = comment.trim();
+            if let Some(request) = comment.strip_prefix("Request:") {
+                messages.push(Message::new(MessageKind::UserRequest {
+                    text: request.trim().to_string(),
+                }));
+            } else if !comment.starts_with("NexSh session:") && !comment.starts_with("Exported ") {
+                notes.push(comment);
+            }
+            continue;
+        }
+        messages.push(Message::new(MessageKind::ProposedCommand {
+            command: line.to_string(),
+            message: notes.join(" "),
+            dangerous: false,
+            category: "other".to_string(),
+        }));
+        notes.clear();
+    }
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_shell_scripts() {
+        let script = "#!/bin/sh\n# NexSh session: default\n# Request: list files\n# Lists everything\nls -la\n\n# Request: disk usage\ndf -h\n";
+        let messages = parse_shell(script);
+        assert_eq!(messages.len(), 4);
+        assert!(matches!(
+            &messages[0].kind,
+            MessageKind::UserRequest { text } if text == "list files"
+        ));
+        assert!(matches!(
+            &messages[1].kind,
+            MessageKind::ProposedCommand { command, message, .. }
+                if command == "ls -la" && message == "Lists everything"
+        ));
+        assert!(matches!(
+            &messages[3].kind,
+            MessageKind::ProposedCommand { command, message, .. }
+                if command == "df -h" && message.is_empty()
+        ));
+    }
+}
@@ -0,0 +1,137 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// A natural-language request, the command it produced and how running it went
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub timestamp: u64,
+    pub request: String,
+    pub command: String,
+    pub dangerous: bool,
+    pub exit_code: Option<i32>,
+    pub cwd: String,
+    pub session: String,
+}
+
+/// Append-only JSON Lines index of executed requests
+pub struct HistoryIndex {
+    path: PathBuf,
+}
+
+impl HistoryIndex {
+    /// Open the index in `config_dir`, keeping at most `max_entries` entries
+    pub fn open(config_dir: &Path, max_entries: usize) -> Result<Self, Box<dyn Error>> {
+        let index = Self {
+            path: config_dir.join("nexsh_requests.jsonl"),
+        };
+        let entries = index.load()?;
+        if entries.len() > max_entries {
+            let mut content = String::new();
+            for entry in &entries[entries.len() - max_entries..] {
+                content.push_str(&serde_json::to_string(entry)?);
+                content.push('\n');
+            }
+            fs::write(&index.path, content)?;
+        }
+        Ok(index)
+    }
+
+    pub fn append(&self, entry: &HistoryEntry) -> Result<(), Box<dyn Error>> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(entry)?)?;
+        Ok(())
+    }
+
+    /// All entries, oldest first; unreadable lines are skipped
+    pub fn load(&self) -> Result<Vec<HistoryEntry>, Box<dyn Error>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        Ok(fs::read_to_string(&self.path)?
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+
+    /// Entries matching `query` best first, paired with their 1-based id
+    pub fn search(
+        &self,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<(usize, HistoryEntry)>, Box<dyn Error>> {
+        let mut matches: Vec<(i64, usize, HistoryEntry)> = self
+            .load()?
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, entry)| {
+                let text = format!("{} {}", entry.request, entry.command);
+                fuzzy_score(query, &text).map(|score| (score, i + 1, entry))
+            })
+            .collect();
+        // Best score first, most recent first among equal scores
+        matches.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.cmp(&a.1)));
+        Ok(matches
+            .into_iter()
+            .take(limit)
+            .map(|(_, id, entry)| (id, entry))
+            .collect())
+    }
+}
+
+/// Score how well `query` matches `text`: every query word must appear as a
+/// case-insensitive subsequence, with bonuses for consecutive characters and
+/// matches at word starts
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut total = 0;
+    for word in query.to_lowercase().split_whitespace() {
+        let mut score = 0;
+        let mut position = 0;
+        let mut previous: Option<usize> = None;
+        for c in word.chars() {
+            let found = text[position..].iter().position(|&t| t == c)? + position;
+            score += 1;
+            if previous == Some(found.wrapping_sub(1)) {
+                score += 5;
+            }
+            if found == 0 || !text[found - 1].is_alphanumeric() {
+                score += 3;
+            }
+            if let Some(previous) = previous {
+                score -= (found - previous - 1).min(5) as i64;
+            }
+            previous = Some(found);
+            position = found + 1;
+        }
+        total += score;
+    }
+    Some(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requires_every_character_in_order() {
+        assert!(fuzzy_score("dkr", "docker ps").is_some());
+        assert!(fuzzy_score("rkd", "docker ps").is_none());
+        assert!(fuzzy_score("docker kill", "docker ps").is_none());
+    }
+
+    #[test]
+    fn prefers_contiguous_matches_at_word_starts() {
+        let contiguous = fuzzy_score("ps", "docker ps").unwrap();
+        let scattered = fuzzy_score("ps", "pgrep -s").unwrap();
+        assert!(contiguous > scattered);
+        assert_eq!(fuzzy_score("PS", "docker ps"), Some(contiguous));
+    }
+}
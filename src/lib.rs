@@ -1,10 +1,6 @@
-use clap::Parser;
+use clap::ValueEnum;
 use colored::*;
 use directories::ProjectDirs;
-use gemini_client_rs::{
-    types::{GenerateContentRequest, PartResponse},
-    GeminiClient,
-};
 use prompt::SYSTEM_PROMPT;
 use rustyline::{error::ReadlineError, Config, DefaultEditor};
 use serde_json::json;
@@ -12,33 +8,65 @@ use std::{
     borrow::Cow,
     error::Error,
     fs,
-    io::{self, Write},
-    path::PathBuf,
-    process::Command,
+    io::{self, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::Instant,
+};
+use types::{
+    Alternative, BudgetAction, CommandReport, CommandResult, FailureAnalysis, GeminiResponse,
+    Message, MessageKind, NexShConfig, OutputSummary, Timing,
 };
-use types::{GeminiResponse, Message, NexShConfig};
 
-use crate::{available_models::list_available_models, prompt::EXPLANATION_PROMPT};
+use crate::{
+    available_models::{ModelCache, ModelInfo},
+    cache::ResponseCache,
+    client::{ApiError, FunctionCall, GeminiClient, GenerateContentResponse},
+    environment::Environment,
+    export::ExportFormat,
+    history::{HistoryEntry, HistoryIndex},
+    project::ProjectInstructions,
+    prompt::{
+        ADAPT_PROMPT, EXPLANATION_PROMPT, FIX_PROMPT, MEMORY_PREFIX, OUTPUT_SUMMARY_PROMPT,
+        PIPED_INPUT_PROMPT, REPAIR_PROMPT, SUMMARY_PROMPT, TOOLS_PROMPT,
+    },
+    routing::Route,
+    runbook::{Runbook, RunbookStep, RunbookStore},
+    session::SessionStore,
+    stream::MessageStream,
+    usage::UsageStore,
+};
 use indicatif::{ProgressBar, ProgressStyle};
 pub mod available_models;
+pub mod cache;
+pub mod client;
+pub mod context;
+pub mod environment;
+pub mod export;
+pub mod history;
+pub mod logging;
+pub mod output;
+pub mod project;
 pub mod prompt;
+pub mod redact;
+pub mod routing;
+pub mod runbook;
+pub mod session;
+pub mod stream;
+pub mod tools;
 pub mod types;
+pub mod usage;
 
-#[derive(Parser, Debug)]
-#[command(
-    name = "nexsh",
-    version = "0.8.1",
-    about = "Next-generation AI-powered shell using Google Gemini"
-)]
-struct Args {
-    /// Initialize configuration
-    #[arg(short, long)]
-    init: bool,
-
-    /// Execute single command and exit
-    #[arg(short, long)]
-    execute: Option<String>,
-}
+/// How long a fetched model list is reused before asking the API again
+const MODEL_CACHE_SECS: u64 = 24 * 60 * 60;
+/// Number of entries shown by the `history` builtin
+const HISTORY_LIST_LIMIT: usize = 20;
+
+/// Shown instead of the API key when printing settings
+const MASKED_API_KEY: &str = "********";
+
+/// Maximum bytes of a command output sent to the model for summarization
+const MAX_SUMMARIZED_OUTPUT_BYTES: usize = 64 * 1024;
 
 impl Default for NexShConfig {
     fn default() -> Self {
@@ -46,7 +74,42 @@ impl Default for NexShConfig {
             api_key: String::new(),
             history_size: 1000,
             max_context_messages: 100,
+            context_token_budget: 16000,
+            output_head_lines: 40,
+            output_tail_lines: 20,
+            max_output_bytes: 8 * 1024,
+            summarize_large_output: false,
+            max_stdin_bytes: 1024 * 1024,
             model: Some("gemini-2.0-flash".to_string()),
+            fast_model: None,
+            strong_model: None,
+            auto_fix: false,
+            max_fix_attempts: 3,
+            enable_tools: true,
+            max_tool_calls: 8,
+            tools_read_anywhere: false,
+            environment_context: true,
+            context_tools: [
+                "git", "docker", "podman", "kubectl", "cargo", "npm", "python3",
+            ]
+            .iter()
+            .map(|tool| tool.to_string())
+            .collect(),
+            context_listing_entries: 30,
+            system_prompt: None,
+            explanation_prompt: None,
+            fix_prompt: None,
+            session: session::DEFAULT_SESSION.to_string(),
+            auto_session: false,
+            cache_enabled: true,
+            cache_ttl_secs: 7 * 24 * 60 * 60,
+            cache_max_entries: 500,
+            request_timeout_secs: 60,
+            max_retries: 3,
+            stream_responses: true,
+            pricing: usage::default_pricing(),
+            monthly_budget: None,
+            budget_action: BudgetAction::Warn,
         }
     }
 }
@@ -55,7 +118,31 @@ pub struct NexSh {
     config: NexShConfig,
     config_dir: PathBuf,
     history_file: PathBuf,
-    context_file: PathBuf,
+    sessions: SessionStore,
+    runbooks: RunbookStore,
+    history: HistoryIndex,
+    cache: ResponseCache,
+    models: ModelCache,
+    usage: UsageStore,
+    /// Bypass the response cache for this run
+    no_cache: bool,
+    /// Only show commands, never run them
+    dry_run: bool,
+    /// Answer confirmations with yes, except for dangerous commands
+    assume_yes: bool,
+    /// Model used for this run instead of the configured and routed ones
+    model_override: Option<String>,
+    /// Exit code of the last executed command
+    last_exit_code: Option<i32>,
+    /// No spinners, streamed replies or echoed command output, for
+    /// machine-readable output
+    quiet: bool,
+    /// Data piped to nexsh, passed on to the stdin of executed commands
+    piped_input: Option<Vec<u8>>,
+    /// Redacted view of `piped_input` to attach to the next request
+    piped_note: Option<String>,
+    recording: Option<Runbook>,
+    session: String,
     client: GeminiClient,
     editor: DefaultEditor,
     messages: Vec<Message>,
@@ -64,6 +151,9 @@ pub struct NexSh {
 impl NexSh {
     /// Helper to create and configure a spinner progress bar with a colored message
     fn set_progress_message(&self, message: impl Into<Cow<'static, str>>) -> ProgressBar {
+        if self.quiet {
+            return ProgressBar::hidden();
+        }
         let pb = ProgressBar::new_spinner();
         let spinner_style = ProgressStyle::with_template("{spinner} {wide_msg}")
             .unwrap()
@@ -73,6 +163,147 @@ impl NexSh {
         pb.set_message(message);
         pb
     }
+    /// Models the API key can use: the cached list while it is fresh, otherwise
+    /// fetched from the models endpoint, falling back to a stale cache or the
+    /// built-in list when that fails
+    async fn available_models(&self, refresh: bool) -> Vec<ModelInfo> {
+        if !refresh {
+            if let Some(models) = self.models.load(MODEL_CACHE_SECS) {
+                return models;
+            }
+        }
+        let pb = self.set_progress_message("Fetching models...".blue().to_string());
+        let fetched = self.client.list_models().await;
+        pb.finish_and_clear();
+        match fetched {
+            Ok(models) if !models.is_empty() => {
+                let _ = self.models.save(&models);
+                models
+            }
+            Ok(_) => available_models::static_models(),
+            Err(e) => {
+                eprintln!(
+                    "{} {}",
+                    "⚠️ Could not fetch the model list, showing the last known models:".yellow(),
+                    e
+                );
+                self.models
+                    .load(u64::MAX)
+                    .unwrap_or_else(available_models::static_models)
+            }
+        }
+    }
+
+    /// List the available models with their capabilities and ask for one by
+    /// number or name; `None` keeps the current model
+    async fn choose_model(&mut self, refresh: bool) -> Result<Option<String>, Box<dyn Error>> {
+        let models = self.available_models(refresh).await;
+        let current = self
+            .config
+            .model
+            .clone()
+            .unwrap_or_else(|| "gemini-2.0-flash".to_string());
+        println!("Available Gemini models:");
+        for (i, model) in models.iter().enumerate() {
+            let marker = if model.name == current {
+                " (current)".green().to_string()
+            } else {
+                String::new()
+            };
+            println!(
+                "  {}. {}{} {}",
+                i + 1,
+                model.name,
+                marker,
+                format!("- {} ({})", model.display_name, model.capabilities()).bright_black()
+            );
+        }
+
+        let prompt = format!(
+            "Select model by number or name (Enter to keep {}): ",
+            current
+        );
+        loop {
+            let Ok(input) = self.editor.readline(&prompt) else {
+                return Ok(None);
+            };
+            let choice = input.trim();
+            if choice.is_empty() {
+                return Ok(None);
+            }
+            let selected = match choice.parse::<usize>() {
+                Ok(index) => index.checked_sub(1).and_then(|i| models.get(i)),
+                Err(_) => models.iter().find(|m| m.name == choice).or_else(|| {
+                    let mut matches = models.iter().filter(|m| m.name.starts_with(choice));
+                    // Only accept a prefix that names a single model
+                    matches.next().filter(|_| matches.next().is_none())
+                }),
+            };
+            match selected {
+                Some(model) => return Ok(Some(model.name.clone())),
+                None => println!(
+                    "{}",
+                    format!("Unknown model '{}', pick a number from the list.", choice).red()
+                ),
+            }
+        }
+    }
+
+    /// List the available models and save the one picked as the default
+    pub async fn models_command(&mut self, refresh: bool) -> Result<(), Box<dyn Error>> {
+        if let Some(model) = self.choose_model(refresh).await? {
+            self.set_model(&model)?;
+        }
+        Ok(())
+    }
+
+    /// Print a setting, or all of them, with the API key masked
+    pub fn config_get(&self, key: Option<&str>) -> Result<(), Box<dyn Error>> {
+        let mut config = serde_json::to_value(&self.config)?;
+        if !self.config.api_key.is_empty() {
+            config["api_key"] = json!(MASKED_API_KEY);
+        }
+        let Some(key) = key else {
+            println!("{}", serde_json::to_string_pretty(&config)?);
+            return Ok(());
+        };
+        match config.get(key) {
+            Some(serde_json::Value::String(value)) => println!("{}", value),
+            Some(value) => println!("{}", serde_json::to_string_pretty(value)?),
+            None => return Err(format!("Unknown setting '{}'", key).into()),
+        }
+        Ok(())
+    }
+
+    /// Change a setting and save the config. Values are read as JSON when they
+    /// parse, so numbers, booleans, lists and `null` keep their type
+    pub fn config_set(&mut self, key: &str, value: &str) -> Result<(), Box<dyn Error>> {
+        let mut config = serde_json::to_value(&self.config)?;
+        let current = config
+            .get(key)
+            .ok_or_else(|| format!("Unknown setting '{}'", key))?;
+        let parsed = if current.is_string() {
+            json!(value)
+        } else {
+            serde_json::from_str(value).unwrap_or_else(|_| json!(value))
+        };
+        config[key] = parsed;
+        self.config = serde_json::from_value(config)
+            .map_err(|e| format!("Invalid value for {}: {}", key, e))?;
+        self.save_config()?;
+        self.client = GeminiClient::new(
+            self.config.api_key.clone(),
+            self.config.request_timeout_secs,
+        );
+        let shown = if key == "api_key" {
+            MASKED_API_KEY
+        } else {
+            value
+        };
+        println!("✅ Set {} to {}", key, shown.green());
+        Ok(())
+    }
+
     /// Change the Gemini model at runtime and save to config
     pub fn set_model(&mut self, model: &str) -> Result<(), Box<dyn Error>> {
         self.config.model = Some(model.to_string());
@@ -86,44 +317,31 @@ impl NexSh {
 
         let config_dir = proj_dirs.config_dir().to_path_buf();
         fs::create_dir_all(&config_dir)?;
+        logging::init(&config_dir);
 
         let config_file = config_dir.join("nexsh_config.json");
         let history_file = config_dir.join("nexsh_history.txt");
-        let context_file = config_dir.join("nexsh_context.json");
 
         let config = if config_file.exists() {
             let content = fs::read_to_string(&config_file)?;
-            let parsed: serde_json::Value = serde_json::from_str(&content)?;
-            NexShConfig {
-                api_key: parsed
-                    .get("api_key")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .to_string(),
-                history_size: parsed
-                    .get("history_size")
-                    .and_then(|v| v.as_u64())
-                    .unwrap_or(1000) as usize,
-                max_context_messages: parsed
-                    .get("max_context_messages")
-                    .and_then(|v| v.as_u64())
-                    .unwrap_or(100) as usize,
-                model: parsed
-                    .get("model")
-                    .and_then(|v| v.as_str())
-                    .map(|s| s.to_string())
-                    .or(Some("gemini-2.0-flash".to_string())),
-            }
+            serde_json::from_str(&content)?
         } else {
             NexShConfig::default()
         };
 
-        let messages = if context_file.exists() {
-            let content = fs::read_to_string(&context_file)?;
-            serde_json::from_str(&content)?
+        let sessions = SessionStore::open(&config_dir)?;
+        let runbooks = RunbookStore::open(&config_dir)?;
+        let history = HistoryIndex::open(&config_dir, config.history_size)?;
+        let models = ModelCache::open(&config_dir);
+        let usage = UsageStore::open(&config_dir);
+        let cache =
+            ResponseCache::open(&config_dir, config.cache_ttl_secs, config.cache_max_entries);
+        let session = if config.auto_session {
+            session::name_for_dir(&std::env::current_dir()?)
         } else {
-            Vec::new()
+            config.session.clone()
         };
+        let messages = sessions.load(&session)?;
         let editor_config = Config::builder()
             .max_history_size(config.history_size)?
             .build();
@@ -132,13 +350,28 @@ impl NexSh {
             let _ = editor.load_history(&history_file);
         }
 
-        let client = GeminiClient::new(config.api_key.clone());
+        let client = GeminiClient::new(config.api_key.clone(), config.request_timeout_secs);
 
         Ok(Self {
             config,
             config_dir,
             history_file,
-            context_file,
+            sessions,
+            runbooks,
+            history,
+            cache,
+            models,
+            usage,
+            no_cache: false,
+            dry_run: false,
+            assume_yes: false,
+            model_override: None,
+            last_exit_code: None,
+            quiet: false,
+            piped_input: None,
+            piped_note: None,
+            recording: None,
+            session,
             client,
             editor,
             messages,
@@ -153,34 +386,806 @@ impl NexSh {
     }
 
     fn save_context(&self) -> Result<(), Box<dyn Error>> {
-        let content = serde_json::to_string_pretty(&self.messages)?;
-        fs::write(&self.context_file, content)?;
+        self.sessions.save(&self.session, &self.messages)
+    }
+
+    /// Always ask the model instead of reusing cached responses
+    pub fn disable_cache(&mut self) {
+        self.no_cache = true;
+    }
+
+    pub fn enable_dry_run(&mut self) {
+        self.dry_run = true;
+    }
+
+    pub fn assume_yes(&mut self) {
+        self.assume_yes = true;
+    }
+
+    /// Send every request of this run to `model`, unless a request names a
+    /// model inline
+    pub fn use_model(&mut self, model: &str) {
+        self.model_override = Some(model.to_string());
+    }
+
+    /// Exit code of the last command run, to report as the process exit code
+    pub fn last_exit_code(&self) -> Option<i32> {
+        self.last_exit_code
+    }
+
+    pub fn enable_quiet(&mut self) {
+        self.quiet = true;
+    }
+
+    /// Read stdin when it is piped rather than a terminal, so the next request
+    /// can use it as context and executed commands receive it as their input.
+    /// Returns whether anything was read
+    pub fn read_piped_input(&mut self) -> Result<bool, Box<dyn Error>> {
+        let stdin = io::stdin();
+        if stdin.is_terminal() {
+            return Ok(false);
+        }
+        let limit = self.config.max_stdin_bytes;
+        let mut input = Vec::new();
+        stdin
+            .lock()
+            .take(limit as u64 + 1)
+            .read_to_end(&mut input)?;
+        if input.len() > limit {
+            return Err(format!(
+                "Piped input is larger than {} bytes. Raise max_stdin_bytes in the config.",
+                limit
+            )
+            .into());
+        }
+        if input.is_empty() {
+            return Ok(false);
+        }
+
+        let text = redact::secrets(&output::decode(&input));
+        let truncated = output::truncate(
+            &text,
+            self.config.output_head_lines,
+            self.config.output_tail_lines,
+            self.config.max_output_bytes,
+        );
+        log::debug!(
+            "read piped input bytes={} lines={} truncated={}",
+            input.len(),
+            truncated.total_lines,
+            truncated.truncated
+        );
+        self.piped_note = Some(
+            PIPED_INPUT_PROMPT
+                .replace("{LINES}", &truncated.total_lines.to_string())
+                .replace("{INPUT}", &truncated.text),
+        );
+        self.piped_input = Some(input);
+        Ok(true)
+    }
+
+    /// Switch to another named session, creating it on first save
+    pub fn switch_session(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        session::validate_name(name)?;
+        self.messages = self.sessions.load(name)?;
+        self.session = name.to_string();
+        Ok(())
+    }
+
+    /// Write the current session to `path`, or to a generated file name in the
+    /// current directory
+    pub fn export_session(
+        &self,
+        format: ExportFormat,
+        path: Option<&Path>,
+    ) -> Result<PathBuf, Box<dyn Error>> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => PathBuf::from(format!(
+                "nexsh-{}-{}.{}",
+                self.session,
+                chrono::Local::now().format("%Y%m%d-%H%M%S"),
+                format.extension()
+            )),
+        };
+        let content = export::render(&self.messages, &self.session, format)?;
+        fs::write(&path, content)?;
+        Ok(path)
+    }
+
+    /// Seed a new session from an exported transcript and switch to it
+    pub fn import_session(
+        &mut self,
+        path: &Path,
+        name: Option<&str>,
+    ) -> Result<String, Box<dyn Error>> {
+        let format = ExportFormat::from_path(path).unwrap_or(ExportFormat::Json);
+        let messages = export::parse(&fs::read_to_string(path)?, format)?;
+        let name = match name {
+            Some(name) => name.to_string(),
+            None => session::sanitize_name(
+                &path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+            ),
+        };
+        session::validate_name(&name)?;
+        if self.sessions.exists(&name) {
+            return Err(format!("Session '{}' already exists", name).into());
+        }
+        self.sessions.save(&name, &messages)?;
+        self.select_session(&name)?;
+        Ok(name)
+    }
+
+    fn export_command(&self, args: &str) -> Result<(), Box<dyn Error>> {
+        let mut args = args.split_whitespace().peekable();
+        let format = match args.peek().map(|arg| ExportFormat::from_str(arg, true)) {
+            Some(Ok(format)) => {
+                args.next();
+                Some(format)
+            }
+            _ => None,
+        };
+        let path = args.next().map(PathBuf::from);
+        let format = format
+            .or_else(|| path.as_deref().and_then(ExportFormat::from_path))
+            .unwrap_or(ExportFormat::Markdown);
+        let path = self.export_session(format, path.as_deref())?;
+        println!(
+            "✅ Exported session to {}",
+            path.display().to_string().green()
+        );
+        Ok(())
+    }
+
+    fn import_command(&mut self, args: &str) -> Result<(), Box<dyn Error>> {
+        match args.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [path] => self.import_session(Path::new(path), None),
+            [path, name] => self.import_session(Path::new(path), Some(name)),
+            _ => {
+                println!("Usage: import <file.json|file.sh> [session]");
+                return Ok(());
+            }
+        }
+        .map(|name| println!("✅ Imported into session: {}", name.green()))
+    }
+
+    fn record_command(&mut self, args: &str) -> Result<(), Box<dyn Error>> {
+        match args.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [] => match &self.recording {
+                Some(runbook) => println!(
+                    "⏺️ Recording runbook {} ({} steps)",
+                    runbook.name.green(),
+                    runbook.steps.len()
+                ),
+                None => println!("Not recording. Use 'record start <name>' to begin."),
+            },
+            ["start", name] => {
+                session::validate_name(name)?;
+                if let Some(runbook) = &self.recording {
+                    return Err(format!("Already recording runbook '{}'", runbook.name).into());
+                }
+                if self.runbooks.exists(name) {
+                    println!(
+                        "{}",
+                        "Runbook exists and will be overwritten on stop.".yellow()
+                    );
+                }
+                self.recording = Some(Runbook::new(name));
+                println!("⏺️ Recording runbook: {}", name.green());
+            }
+            ["stop"] => {
+                let runbook = self.recording.take().ok_or("Not recording a runbook")?;
+                if runbook.steps.is_empty() {
+                    println!("{}", "No steps recorded, runbook discarded.".yellow());
+                } else {
+                    let path = self.runbooks.save(&runbook)?;
+                    println!(
+                        "✅ Saved runbook {} with {} steps to {}",
+                        runbook.name.green(),
+                        runbook.steps.len(),
+                        path.display()
+                    );
+                }
+            }
+            ["list"] => {
+                for name in self.runbooks.names()? {
+                    println!("  {}", name);
+                }
+            }
+            _ => println!("Usage: record [start <name> | stop | list]"),
+        }
         Ok(())
     }
 
-    fn add_message(&mut self, role: &str, content: &str) {
-        let message = Message {
-            role: role.to_string(),
-            content: content.to_string(),
+    /// The most recent natural-language request in the context
+    fn last_request(&self) -> String {
+        self.messages
+            .iter()
+            .rev()
+            .find_map(|m| match &m.kind {
+                MessageKind::UserRequest { text } => Some(text.clone()),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    /// Explanation and danger flag of the most recent proposal of `command`
+    fn proposal_for(&self, command: &str) -> (String, bool) {
+        self.messages
+            .iter()
+            .rev()
+            .find_map(|m| match &m.kind {
+                MessageKind::ProposedCommand {
+                    command: proposed,
+                    message,
+                    dangerous,
+                    ..
+                } if proposed == command => Some((message.clone(), *dangerous)),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    /// Add a successfully executed command to the runbook being recorded, with the
+    /// request and explanation it came from
+    fn record_step(&mut self, command: &str) {
+        if self.recording.is_none() {
+            return;
+        }
+        let request = self.last_request();
+        let (explanation, dangerous) = self.proposal_for(command);
+        if let Some(runbook) = self.recording.as_mut() {
+            runbook.steps.push(RunbookStep {
+                request,
+                command: command.to_string(),
+                explanation,
+                dangerous,
+            });
+        }
+    }
+
+    /// Link an executed command to the request that produced it in the history index
+    fn record_history(&self, result: &CommandResult) {
+        let entry = HistoryEntry {
             timestamp: std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap()
                 .as_secs(),
+            request: self.last_request(),
+            command: result.command.clone(),
+            dangerous: self.proposal_for(&result.command).1,
+            exit_code: result.exit_code,
+            cwd: std::env::current_dir()
+                .map(|dir| dir.display().to_string())
+                .unwrap_or_default(),
+            session: self.session.clone(),
         };
+        if let Err(e) = self.history.append(&entry) {
+            eprintln!("{} {}", "⚠️ Failed to update history:".yellow(), e);
+        }
+    }
 
-        self.messages.push(message);
+    fn print_history_entry(id: usize, entry: &HistoryEntry) {
+        let time = chrono::DateTime::from_timestamp(entry.timestamp as i64, 0)
+            .map(|time| {
+                time.with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string()
+            })
+            .unwrap_or_default();
+        let status = match entry.exit_code {
+            Some(0) => "✅".to_string(),
+            Some(code) => format!("❌ {}", code),
+            None => "❌".to_string(),
+        };
+        println!(
+            "{} {} {} {}",
+            format!("#{:<4}", id).cyan(),
+            time.bright_black(),
+            status,
+            entry.request
+        );
+        println!("       {} {}", "→".blue(), entry.command);
+    }
 
-        // Trim old messages if we exceed max_context_messages
-        if self.messages.len() > self.config.max_context_messages {
-            self.messages = self
-                .messages
-                .split_off(self.messages.len() - self.config.max_context_messages);
+    pub async fn history_command(&mut self, args: &str) -> Result<(), Box<dyn Error>> {
+        let args = args.trim();
+        if let Some(id) = args.strip_prefix("run ") {
+            let id: usize = id.trim().parse().map_err(|_| "Usage: history run <id>")?;
+            let entry = id
+                .checked_sub(1)
+                .and_then(|i| self.history.load().ok()?.into_iter().nth(i))
+                .ok_or_else(|| format!("No history entry #{}", id))?;
+            return self.rerun_history_entry(entry).await;
         }
 
+        let entries = if args.is_empty() {
+            let entries = self.history.load()?;
+            let skip = entries.len().saturating_sub(HISTORY_LIST_LIMIT);
+            entries
+                .into_iter()
+                .enumerate()
+                .skip(skip)
+                .map(|(i, entry)| (i + 1, entry))
+                .collect()
+        } else {
+            self.history.search(args, HISTORY_LIST_LIMIT)?
+        };
+        if entries.is_empty() {
+            println!("{}", "No matching history entries.".yellow());
+        }
+        for (id, entry) in &entries {
+            Self::print_history_entry(*id, entry);
+        }
+        Ok(())
+    }
+
+    /// Run a command from the history again without asking the model
+    async fn rerun_history_entry(&mut self, entry: HistoryEntry) -> Result<(), Box<dyn Error>> {
+        println!("{} {}", "→".blue(), entry.command);
+        self.add_message(MessageKind::UserRequest {
+            text: entry.request.clone(),
+        });
+        self.add_message(MessageKind::ProposedCommand {
+            command: entry.command.clone(),
+            message: "Rerun from history".to_string(),
+            dangerous: entry.dangerous,
+            category: "other".to_string(),
+        });
+        if !self.confirm("? Run this command again? [y/N]: ")?
+            || (entry.dangerous && !self.confirm_execution()?)
+        {
+            println!("Command execution cancelled.");
+            return Ok(());
+        }
+        self.run_with_fixes(entry.command).await
+    }
+
+    /// Replay a runbook step by step, confirming each one and optionally asking
+    /// the model to adapt the recorded commands to the current machine
+    pub async fn replay_runbook(&mut self, name: &str, adapt: bool) -> Result<(), Box<dyn Error>> {
+        let runbook = self.runbooks.load(name)?;
+        if adapt && self.config.api_key.is_empty() {
+            self.initialize().await?;
+        }
+        println!(
+            "📘 Runbook {} ({} steps)",
+            runbook.name.green(),
+            runbook.steps.len()
+        );
+
+        let total = runbook.steps.len();
+        for (i, step) in runbook.steps.into_iter().enumerate() {
+            println!(
+                "\n{} {}",
+                format!("Step {}/{}:", i + 1, total).cyan(),
+                step.request
+            );
+            self.add_message(MessageKind::UserRequest {
+                text: step.request.clone(),
+            });
+
+            let mut step = step;
+            if adapt {
+                self.add_message(MessageKind::SystemNote {
+                    text: ADAPT_PROMPT
+                        .replace("{COMMAND}", &step.command)
+                        .replace("{RECORDED_OS}", &runbook.os)
+                        .replace("{RECORDED_CWD}", &runbook.cwd),
+                });
+                self.compact_context().await;
+                match self
+                    .request_response(&self.model_for(&Route::Fast), None)
+                    .await
+                {
+                    Ok(adapted) if !adapted.command.is_empty() => {
+                        if adapted.command != step.command {
+                            println!("{} {}", "🔄 Adapted from:".yellow(), step.command);
+                        }
+                        step.command = adapted.command;
+                        step.explanation = adapted.message;
+                        step.dangerous = adapted.dangerous;
+                    }
+                    Ok(_) => println!("{}", "Keeping the recorded command.".yellow()),
+                    Err(e) => println!(
+                        "{} {}",
+                        "Keeping the recorded command, adapting failed:".yellow(),
+                        e
+                    ),
+                }
+            }
+
+            println!("{} {}", "🤖 →".green(), step.explanation.yellow());
+            println!("{} {}", "→".blue(), step.command);
+            self.add_message(MessageKind::ProposedCommand {
+                command: step.command.clone(),
+                message: step.explanation.clone(),
+                dangerous: step.dangerous,
+                category: "other".to_string(),
+            });
+
+            let answer = self
+                .editor
+                .readline(&"? Run this step? [y/N/q]: ".red().to_string())?;
+            match answer.trim().to_lowercase().as_str() {
+                "y" => {}
+                "q" => {
+                    println!("Runbook stopped.");
+                    return Ok(());
+                }
+                _ => {
+                    println!("Step skipped.");
+                    continue;
+                }
+            }
+            if step.dangerous && !self.confirm_execution()? {
+                println!("Step skipped.");
+                continue;
+            }
+            if let Err(e) = self.run_with_fixes(step.command).await {
+                if !self.confirm("? Continue with the next step? [y/N]: ")? {
+                    return Err(e);
+                }
+            }
+        }
+        println!("\n✅ Runbook {} finished", runbook.name.green());
+        Ok(())
+    }
+
+    /// Switch session and make it the default for the next start
+    fn select_session(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        self.switch_session(name)?;
+        if !self.config.auto_session {
+            self.config.session = name.to_string();
+            self.save_config()?;
+        }
+        Ok(())
+    }
+
+    pub fn session_command(&mut self, args: &str) -> Result<(), Box<dyn Error>> {
+        let args: Vec<&str> = args.split_whitespace().collect();
+        match args.as_slice() {
+            [] | ["list"] => {
+                let sessions = self.sessions.list()?;
+                if !sessions.iter().any(|s| s.name == self.session) {
+                    println!("{} {} (new)", "*".green(), self.session.green());
+                }
+                for info in sessions {
+                    let modified = info
+                        .modified
+                        .map(|time| {
+                            chrono::DateTime::<chrono::Local>::from(time)
+                                .format("%Y-%m-%d %H:%M")
+                                .to_string()
+                        })
+                        .unwrap_or_default();
+                    if info.name == self.session {
+                        println!(
+                            "{} {} ({} messages, {})",
+                            "*".green(),
+                            info.name.green(),
+                            info.messages,
+                            modified.bright_black()
+                        );
+                    } else {
+                        println!(
+                            "  {} ({} messages, {})",
+                            info.name,
+                            info.messages,
+                            modified.bright_black()
+                        );
+                    }
+                }
+            }
+            ["new", name] => {
+                if self.sessions.exists(name) {
+                    return Err(format!("Session '{}' already exists", name).into());
+                }
+                self.select_session(name)?;
+                self.save_context()?;
+                println!("✅ Created session: {}", name.green());
+            }
+            ["switch", name] => {
+                if !self.sessions.exists(name) {
+                    return Err(format!(
+                        "Session '{}' does not exist, create it with 'session new {}'",
+                        name, name
+                    )
+                    .into());
+                }
+                self.select_session(name)?;
+                println!("✅ Switched to session: {}", name.green());
+            }
+            ["delete", name] => {
+                self.sessions.delete(name)?;
+                if *name == self.session {
+                    self.select_session(session::DEFAULT_SESSION)?;
+                }
+                println!("🗑️ Deleted session: {}", name.yellow());
+            }
+            ["rename", from, to] => {
+                self.sessions.rename(from, to)?;
+                if *from == self.session {
+                    self.select_session(to)?;
+                }
+                println!("✅ Renamed session {} to {}", from.yellow(), to.green());
+            }
+            _ => {
+                println!("Usage: session [list | new <name> | switch <name> | delete <name> | rename <old> <new>]");
+            }
+        }
+        Ok(())
+    }
+
+    fn add_message(&mut self, kind: MessageKind) {
+        self.messages.push(Message::new(kind));
         let _ = self.save_context();
     }
 
-    pub fn initialize(&mut self) -> Result<(), Box<dyn Error>> {
+    /// Fold the oldest messages into a summary once the context exceeds the token
+    /// budget or `max_context_messages`, dropping them if summarization fails
+    async fn compact_context(&mut self) {
+        let count = context::messages_to_compact(
+            &self.messages,
+            self.config.context_token_budget,
+            self.config.max_context_messages,
+        );
+        if count == 0 {
+            return;
+        }
+
+        let pb =
+            self.set_progress_message("Summarizing earlier conversation...".blue().to_string());
+        let summary = self.summarize_messages(&self.messages[..count]).await;
+        pb.finish_and_clear();
+
+        let timestamp = self.messages[count - 1].timestamp;
+        let remaining = self.messages.split_off(count);
+        self.messages = match summary {
+            Ok(summary) => {
+                let mut messages = vec![Message {
+                    kind: MessageKind::SystemNote {
+                        text: format!("{}\n{}", MEMORY_PREFIX, summary.trim()),
+                    },
+                    timestamp,
+                }];
+                messages.extend(remaining);
+                messages
+            }
+            Err(e) => {
+                eprintln!(
+                    "{} {}",
+                    "⚠️ Dropping old messages, summarization failed:".yellow(),
+                    e
+                );
+                remaining
+            }
+        };
+        let _ = self.save_context();
+    }
+
+    async fn summarize_messages(&self, messages: &[Message]) -> Result<String, Box<dyn Error>> {
+        let prompt = SUMMARY_PROMPT.replace("{TRANSCRIPT}", &context::transcript(messages));
+        self.generate_once(&prompt, None)
+            .await?
+            .ok_or_else(|| "Empty summary".into())
+    }
+
+    /// Send a single prompt outside the conversation and return its text,
+    /// constrained to `schema` when given
+    async fn generate_once(
+        &self,
+        prompt: &str,
+        schema: Option<serde_json::Value>,
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        let mut req_json = json!({
+            "contents": [{
+                "parts": [{
+                    "text": prompt
+                }],
+                "role": "user"
+            }],
+            "tools": []
+        });
+        if let Some(schema) = schema {
+            req_json["generationConfig"] = json!({
+                "responseMimeType": "application/json",
+                "responseSchema": schema
+            });
+        }
+
+        // Summaries and explanations are simple enough for the fast model
+        let model = self.model_for(&Route::Fast);
+        let response = self.generate(&model, &req_json, None).await?;
+        Ok(response
+            .candidate()
+            .map(|candidate| candidate.text().trim().to_string())
+            .filter(|text| !text.is_empty()))
+    }
+
+    /// Stream a request, falling back to a regular one when streaming fails
+    /// before anything was shown, e.g. for models or proxies without streaming
+    async fn generate_streaming(
+        &self,
+        model: &str,
+        request: &serde_json::Value,
+        pb: &ProgressBar,
+        stream: &mut MessageStream,
+    ) -> Result<GenerateContentResponse, ApiError> {
+        let started = Instant::now();
+        let result = self
+            .client
+            .stream_generate_content(model, request, |delta| {
+                pb.finish_and_clear();
+                stream.push(delta);
+            })
+            .await;
+        stream.finish();
+        log_request(model, request, true, &result, started);
+        match result {
+            Err(ApiError::Auth(message)) => Err(ApiError::Auth(message)),
+            Err(ApiError::Safety(reason)) => Err(ApiError::Safety(reason)),
+            Err(e) if !stream.message_shown => {
+                log::info!("streaming failed, retrying without streaming: {}", e);
+                self.generate(model, request, Some(pb)).await
+            }
+            Ok(response) => {
+                self.record_usage(model, &response);
+                Ok(response)
+            }
+            result => result,
+        }
+    }
+
+    /// Add the tokens of a response to the session, monthly and model counters
+    fn record_usage(&self, model: &str, response: &GenerateContentResponse) {
+        let Some(metadata) = &response.usage_metadata else {
+            return;
+        };
+        let cost = usage::price_for(&self.config.pricing, model)
+            .map(|price| {
+                (metadata.prompt_token_count as f64 * price.input_per_million
+                    + metadata.candidates_token_count as f64 * price.output_per_million)
+                    / 1_000_000.0
+            })
+            .unwrap_or(0.0);
+        let month = chrono::Local::now().format("%Y-%m").to_string();
+        if let Err(e) = self.usage.record(
+            &self.session,
+            &month,
+            model,
+            metadata.prompt_token_count,
+            metadata.candidates_token_count,
+            cost,
+        ) {
+            eprintln!("{} {}", "⚠️ Failed to update usage:".yellow(), e);
+        }
+    }
+
+    /// Warn about or refuse new requests once this month's estimated cost
+    /// reaches `monthly_budget`
+    fn check_budget(&self) -> Result<(), Box<dyn Error>> {
+        let Some(budget) = self.config.monthly_budget else {
+            return Ok(());
+        };
+        let month = chrono::Local::now().format("%Y-%m").to_string();
+        let spent = self
+            .usage
+            .load()
+            .months
+            .get(&month)
+            .map(|counters| counters.cost)
+            .unwrap_or(0.0);
+        if spent < budget {
+            return Ok(());
+        }
+        match self.config.budget_action {
+            BudgetAction::Warn => {
+                let notice = format!(
+                    "⚠️ Monthly budget exceeded: ${:.2} of ${:.2} spent",
+                    spent, budget
+                )
+                .yellow();
+                // Keep stdout machine-readable in quiet mode
+                if self.quiet {
+                    eprintln!("{}", notice);
+                } else {
+                    println!("{}", notice);
+                }
+                Ok(())
+            }
+            BudgetAction::Block => Err(format!(
+                "Monthly budget exceeded: ${:.2} of ${:.2} spent. Raise monthly_budget or set budget_action to \"warn\" in the config.",
+                spent, budget
+            )
+            .into()),
+        }
+    }
+
+    fn usage_command(&self) {
+        let usage = self.usage.load();
+        let month = chrono::Local::now().format("%Y-%m").to_string();
+        let line = |label: &str, counters: &usage::Counters| {
+            println!(
+                "  {:<24} {} requests, {} prompt + {} response tokens, ~${:.4}",
+                label,
+                counters.requests,
+                usage::format_tokens(counters.prompt_tokens),
+                usage::format_tokens(counters.response_tokens),
+                counters.cost
+            );
+        };
+        let session = usage
+            .sessions
+            .get(&self.session)
+            .copied()
+            .unwrap_or_default();
+        let this_month = usage.months.get(&month).copied().unwrap_or_default();
+
+        println!("{}", "📊 Usage (estimated cost)".blue());
+        line(&format!("Session '{}'", self.session), &session);
+        line(&format!("This month ({})", month), &this_month);
+        line("All time", &usage.total);
+        if let Some(budget) = self.config.monthly_budget {
+            println!(
+                "  {:<24} ${:.2} of ${:.2} this month ({:?} when exceeded)",
+                "Budget", this_month.cost, budget, self.config.budget_action
+            );
+        }
+        if !usage.models.is_empty() {
+            println!("{}", "By model:".blue());
+            for (model, counters) in &usage.models {
+                line(model, counters);
+            }
+        }
+    }
+
+    /// Send a request, retrying rate limits, network errors and timeouts with
+    /// exponential backoff; retry notices go through `pb` when a spinner is shown
+    async fn generate(
+        &self,
+        model: &str,
+        request: &serde_json::Value,
+        pb: Option<&ProgressBar>,
+    ) -> Result<GenerateContentResponse, ApiError> {
+        let mut attempt = 0;
+        loop {
+            let started = Instant::now();
+            let result = self.client.generate_content(model, request).await;
+            log_request(model, request, false, &result, started);
+            match result {
+                Err(e) if e.is_transient() && attempt < self.config.max_retries => {
+                    attempt += 1;
+                    let delay = std::time::Duration::from_secs(1 << (attempt - 1));
+                    let notice = format!(
+                        "⏳ {} Retrying in {}s ({}/{})",
+                        e,
+                        delay.as_secs(),
+                        attempt,
+                        self.config.max_retries
+                    )
+                    .yellow()
+                    .to_string();
+                    match pb {
+                        Some(pb) => pb.println(notice),
+                        None => eprintln!("{}", notice),
+                    }
+                    tokio::time::sleep(delay).await;
+                }
+                result => {
+                    if let Ok(response) = &result {
+                        self.record_usage(model, response);
+                    }
+                    return result;
+                }
+            }
+        }
+    }
+
+    pub async fn initialize(&mut self) -> Result<(), Box<dyn Error>> {
         println!("🤖 Welcome to NexSh Setup!");
 
         let input = self
@@ -206,45 +1211,238 @@ impl NexSh {
             }
         }
 
-        // Model selection
-        let models = list_available_models();
-        println!("Available Gemini models:");
-        for (i, m) in models.iter().enumerate() {
-            println!("  {}. {}", i + 1, m);
-        }
-        let input = self
+        if let Ok(input) = self
             .editor
-            .readline("Select Gemini model by number or name (default 1): ")?;
-        let model = input.trim();
-        let selected = if model.is_empty() {
-            models[0]
-        } else if let Ok(idx) = model.parse::<usize>() {
-            models
-                .get(idx.saturating_sub(1))
-                .copied()
-                .unwrap_or(models[0])
-        } else {
-            models
-                .iter()
-                .find(|m| m.starts_with(model))
-                .copied()
-                .unwrap_or(models[0])
-        };
-        self.config.model = Some(selected.to_string());
+            .readline("Suggest fixes automatically when a command fails? [y/N]: ")
+        {
+            self.config.auto_fix = input.trim().eq_ignore_ascii_case("y");
+        }
+
+        // The new key is needed to list the models it can use
+        self.client = GeminiClient::new(
+            self.config.api_key.clone(),
+            self.config.request_timeout_secs,
+        );
+        if let Some(model) = self.choose_model(false).await? {
+            self.config.model = Some(model);
+        }
         self.save_config()?;
         println!("✅ Configuration saved successfully!");
         Ok(())
     }
 
     pub async fn process_command(&mut self, input: &str) -> Result<(), Box<dyn Error>> {
+        let (response, _, cached, stream) = self.propose(input).await?;
+        self.handle_response(response, cached, &stream).await
+    }
+
+    /// Answer `input` without asking anything and report the reply, and the
+    /// result of its command unless it is dangerous, forbidden or execution is
+    /// disabled
+    pub async fn report(&mut self, input: &str) -> Result<CommandReport, Box<dyn Error>> {
+        let started = Instant::now();
+        let (response, model, cached, _) = self.propose(input).await?;
+        let mut report = CommandReport {
+            message: response.message.clone(),
+            model,
+            cached,
+            timing: Timing {
+                generation_ms: started.elapsed().as_millis() as u64,
+                ..Timing::default()
+            },
+            ..CommandReport::default()
+        };
+        if response.command.is_empty() {
+            self.add_message(MessageKind::AssistantResponse {
+                text: response.message,
+            });
+            report.timing.total_ms = started.elapsed().as_millis() as u64;
+            return Ok(report);
+        }
+
+        report.command = Some(response.command.clone());
+        report.category = Some(response.category.clone());
+        report.risk = Some(
+            if response.dangerous {
+                "dangerous"
+            } else {
+                "safe"
+            }
+            .to_string(),
+        );
+        report.alternatives = response.alternatives.clone();
+        report.skipped = if self.dry_run {
+            Some("dry-run")
+        } else if response.dangerous {
+            Some("dangerous")
+        } else if self
+            .project_instructions()
+            .forbidden_in(&response.command)
+            .is_some()
+        {
+            Some("forbidden")
+        } else if cached && !self.assume_yes {
+            // Cached replies may come from another conversation, run them only when asked to
+            Some("cached")
+        } else {
+            None
+        }
+        .map(str::to_string);
+        self.add_message(MessageKind::ProposedCommand {
+            command: response.command.clone(),
+            message: response.message,
+            dangerous: response.dangerous,
+            category: response.category,
+        });
+
+        if report.skipped.is_none() {
+            let executed = Instant::now();
+            let result = self.execute_command(&response.command)?;
+            report.timing.execution_ms = Some(executed.elapsed().as_millis() as u64);
+            log_execution(&result, executed);
+            self.last_exit_code = result.exit_code;
+            self.record_history(&result);
+            self.add_execution_result(&result).await;
+            if result.success() {
+                self.record_step(&result.command);
+            }
+            report.executed = true;
+            report.exit_code = result.exit_code;
+            report.stdout = Some(result.stdout);
+            report.stderr = Some(result.stderr);
+        }
+        report.timing.total_ms = started.elapsed().as_millis() as u64;
+        Ok(report)
+    }
+
+    /// Get the reply to `input`, from the cache when possible, together with
+    /// the model it was routed to, whether it was cached and what was already
+    /// rendered while streaming
+    async fn propose(
+        &mut self,
+        input: &str,
+    ) -> Result<(GeminiResponse, String, bool, MessageStream), Box<dyn Error>> {
         if self.config.api_key.is_empty() {
-            self.initialize()?;
+            if self.quiet {
+                return Err("No Gemini API key configured, run nexsh and enter 'init'".into());
+            }
+            self.initialize().await?;
         }
 
-        let os = std::env::consts::OS.to_string();
-        let prompt = SYSTEM_PROMPT.replace("{OS}", &os);
+        self.check_budget()?;
+
+        let (request, route) = routing::parse_override(input);
+        if request.is_empty() {
+            return Err("Nothing to ask after the model override".into());
+        }
+        let route = route.unwrap_or_else(|| routing::classify(&request));
+        let model = self.model_for(&route);
+        log::debug!("routed request route={:?} model={}", route, model);
+        if Some(&model) != self.config.model.as_ref()
+            && self.model_override.is_none()
+            && !self.quiet
+        {
+            println!("{}", format!("🧭 Using {}", model).bright_black());
+        }
+
+        let piped_note = self.piped_note.take();
+        if let Some(note) = &piped_note {
+            self.add_message(MessageKind::SystemNote { text: note.clone() });
+        }
+        self.add_message(MessageKind::UserRequest {
+            text: request.clone(),
+        });
+        self.compact_context().await;
+
+        let key = (self.config.cache_enabled && !self.no_cache)
+            .then(|| self.cache_key(&request, &model, piped_note.as_deref()));
+        if let Some(response) = key.as_deref().and_then(|key| self.cache.get(key)) {
+            log::debug!("cache hit command={:?}", redact::secrets(&response.command));
+            if !self.quiet {
+                println!(
+                    "{}",
+                    "⚡ Cached response, run with --no-cache to ask again".dimmed()
+                );
+            }
+            return Ok((response, model, true, MessageStream::default()));
+        }
+
+        let mut stream = MessageStream::default();
+        let streamed = self.config.stream_responses && !self.quiet;
+        let response = self
+            .request_response(&model, streamed.then_some(&mut stream))
+            .await?;
+        // Only command proposals are cached, conversational answers depend on
+        // the conversation more than on the request
+        if let Some(key) = key {
+            if !response.command.is_empty() {
+                if let Err(e) = self.cache.put(&key, &request, &response) {
+                    eprintln!("{} {}", "⚠️ Failed to update cache:".yellow(), e);
+                }
+            }
+        }
+        Ok((response, model, false, stream))
+    }
+
+    /// Cache key for a request: the normalized request plus everything in the
+    /// environment that decides which command fits it, including piped input
+    fn cache_key(&self, request: &str, model: &str, piped: Option<&str>) -> String {
+        let environment = Environment::gather_basic(&self.config.context_tools);
+        let context = format!(
+            "{}|{}|{}|{}",
+            model,
+            environment.fingerprint(),
+            self.project_instructions().render(),
+            piped.unwrap_or_default()
+        );
+        cache::cache_key(request, &context)
+    }
+
+    /// Model a route resolves to: a model named inline, then `--model`, then
+    /// the fast or strong model; unset fast and strong models fall back to the
+    /// default model
+    fn model_for(&self, route: &Route) -> String {
+        let default = self.config.model.as_deref().unwrap_or("gemini-2.0-flash");
+        match (route, self.model_override.as_deref()) {
+            (Route::Model(model), _) => model,
+            (_, Some(model)) => model,
+            (Route::Fast, None) => self.config.fast_model.as_deref().unwrap_or(default),
+            (Route::Strong, None) => self.config.strong_model.as_deref().unwrap_or(default),
+        }
+        .to_string()
+    }
 
-        self.add_message("user", input);
+    /// Send the conversation to `model` and parse its structured reply, rendering
+    /// it while it arrives when a `stream` is given
+    async fn request_response(
+        &self,
+        model: &str,
+        mut stream: Option<&mut MessageStream>,
+    ) -> Result<GeminiResponse, Box<dyn Error>> {
+        let os = std::env::consts::OS.to_string();
+        let environment = if self.config.environment_context {
+            Environment::gather(
+                &self.config.context_tools,
+                self.config.context_listing_entries,
+            )
+            .render()
+        } else {
+            String::new()
+        };
+        let mut prompt = self
+            .config
+            .system_prompt
+            .as_deref()
+            .unwrap_or(SYSTEM_PROMPT)
+            .replace("{OS}", &os)
+            .replace("{ENVIRONMENT}", &environment);
+        let project = self.project_instructions();
+        if !project.is_empty() {
+            prompt.push_str(&project.render());
+        }
+        if self.config.enable_tools {
+            prompt.push_str(TOOLS_PROMPT);
+        }
 
         // Create contents array with history messages in correct format
         let mut contents = Vec::new();
@@ -253,18 +1451,36 @@ impl NexSh {
         for msg in &self.messages {
             contents.push(json!({
                 "parts": [{
-                    "text": msg.content
+                    "text": msg.text()
                 }],
-                "role": msg.role
+                "role": msg.gemini_role()
             }));
         }
 
-        let req_json = json!({
-            "generationConfig": {
+        let mut req_json = json!({
+            "system_instruction": {
+                "parts": [
+                    {
+                        "text": prompt
+                    }
+                ],
+                "role": "system"
+            },
+            "contents": contents,
+            "tools": []
+        });
+        if self.config.enable_tools {
+            // Gemini rejects JSON response mode combined with function calling,
+            // so the response format is described in the prompt instead
+            req_json["tools"] = tools::declarations();
+        } else {
+            req_json["generationConfig"] = json!({
                 "responseMimeType": "application/json",
                 "responseSchema": {
                     "type": "object",
                     "required": ["message", "command", "dangerous", "category"],
+                    // Stream the message first, then the command
+                    "propertyOrdering": ["message", "command", "dangerous", "category", "alternatives"],
                     "properties": {
                         "message": {
                             "type": "string",
@@ -282,196 +1498,647 @@ impl NexSh {
                         "category": {
                             "type": "string",
                             "description": "Classification of the command type",
-                            "enum": ["system", "file", "network", "package", "text", "process", "other"]
+                            "enum": types::CATEGORIES
+                        },
+                        "alternatives": {
+                            "type": "array",
+                            "description": "Other commands with real trade-offs, usually empty",
+                            "items": {
+                                "type": "object",
+                                "required": ["command", "description", "dangerous"],
+                                "properties": {
+                                    "command": { "type": "string" },
+                                    "description": {
+                                        "type": "string",
+                                        "description": "Short trade-off compared to the main command"
+                                    },
+                                    "dangerous": { "type": "boolean" }
+                                }
+                            }
                         }
                     }
                 },
-            },
-            "system_instruction": {
-                "parts": [
-                    {
-                        "text": prompt
+            });
+        }
+
+        let pb = self.set_progress_message("Thinking...".yellow().to_string());
+        let mut tool_calls = 0;
+        let mut reasked = false;
+        loop {
+            let response = loop {
+                let response = match stream.as_deref_mut() {
+                    Some(stream) => self.generate_streaming(model, &req_json, &pb, stream).await,
+                    None => self.generate(model, &req_json, Some(&pb)).await,
+                };
+                let response = match response {
+                    Ok(response) => response,
+                    Err(e) => {
+                        pb.finish_and_clear();
+                        return Err(e.into());
                     }
-                ],
-                "role": "system"
-            },
-            "contents": contents,
-            "tools": []
+                };
+                let calls: Vec<FunctionCall> = response
+                    .candidate()
+                    .map(|candidate| candidate.function_calls())
+                    .unwrap_or_default();
+                if calls.is_empty() {
+                    break response;
+                }
+
+                // Run the requested tools and hand their results back to the model
+                let mut call_parts = Vec::new();
+                let mut result_parts = Vec::new();
+                for call in calls {
+                    tool_calls += 1;
+                    pb.println(
+                        format!("🔎 {}({})", call.name, call.args)
+                            .bright_black()
+                            .to_string(),
+                    );
+                    let result =
+                        tools::call(&call.name, &call.args, self.config.tools_read_anywhere);
+                    log::debug!(
+                        "tool call name={} args={} result_bytes={}",
+                        call.name,
+                        redact::secrets(&call.args.to_string()),
+                        result.to_string().len()
+                    );
+                    call_parts.push(json!({
+                        "functionCall": { "name": call.name, "args": call.args }
+                    }));
+                    result_parts.push(json!({
+                        "functionResponse": { "name": call.name, "response": { "content": result } }
+                    }));
+                }
+                if let Some(contents) = req_json["contents"].as_array_mut() {
+                    contents.push(json!({ "parts": call_parts, "role": "model" }));
+                    contents.push(json!({ "parts": result_parts, "role": "user" }));
+                }
+                // Force a final answer once the tool budget is spent
+                if tool_calls >= self.config.max_tool_calls {
+                    req_json["tools"] = json!([]);
+                }
+            };
+
+            let text = response
+                .candidate()
+                .map(|candidate| candidate.text())
+                .unwrap_or_default();
+            match GeminiResponse::parse(&text) {
+                Ok(parsed) => {
+                    pb.finish_and_clear();
+                    log::debug!(
+                        "parsed response model={} category={} dangerous={} alternatives={} command={:?}",
+                        model,
+                        parsed.category,
+                        parsed.dangerous,
+                        parsed.alternatives.len(),
+                        redact::secrets(&parsed.command)
+                    );
+                    return Ok(parsed);
+                }
+                // Give the model one chance to correct a reply that couldn't be parsed
+                Err(error) if !reasked => {
+                    log::warn!(
+                        "unparsable response, asking for a correction: {} raw_chars={}",
+                        error,
+                        text.chars().count()
+                    );
+                    log::debug!("unparsable response raw={:?}", redact::secrets(&text));
+                    reasked = true;
+                    if let Some(contents) = req_json["contents"].as_array_mut() {
+                        contents.push(json!({ "parts": [{ "text": text }], "role": "model" }));
+                        contents.push(json!({
+                            "parts": [{ "text": REPAIR_PROMPT.replace("{ERROR}", &error) }],
+                            "role": "user"
+                        }));
+                    }
+                    if let Some(stream) = stream.as_deref_mut() {
+                        stream.restart();
+                    }
+                    pb.set_message("Reformatting response...".yellow().to_string());
+                }
+                Err(error) => {
+                    pb.finish_and_clear();
+                    if !self.quiet {
+                        println!("Raw response: {}", text.trim());
+                    }
+                    log::error!(
+                        "unparsable response after correction: {} markdown_block={} raw_chars={}",
+                        error,
+                        text.contains("```"),
+                        text.chars().count()
+                    );
+                    log::debug!("unparsable response raw={:?}", redact::secrets(&text));
+                    return Err(format!("Failed to parse response: {}", error).into());
+                }
+            }
+        }
+    }
+
+    /// Show a response, except for what `stream` already rendered, and run its
+    /// command; cached commands were proposed for an earlier conversation, so
+    /// they always need confirmation
+    async fn handle_response(
+        &mut self,
+        response: GeminiResponse,
+        cached: bool,
+        stream: &MessageStream,
+    ) -> Result<(), Box<dyn Error>> {
+        let marker = if cached {
+            "🤖 (cached) →"
+        } else {
+            "🤖 →"
+        };
+        if !stream.message_shown {
+            println!("{} {}", marker.green(), response.message.yellow());
+        }
+        if response.command.is_empty() {
+            // Add model response to context
+            self.add_message(MessageKind::AssistantResponse {
+                text: response.message,
+            });
+            return Ok(());
+        }
+        println!("{} {}", "Category : ".green(), response.category.yellow());
+
+        if self.dry_run {
+            if !stream.command_shown {
+                println!("{} {}", "→".blue(), response.command);
+            }
+            for alternative in &response.alternatives {
+                println!(
+                    "{} {} {}",
+                    "  or".blue(),
+                    alternative.command,
+                    alternative.description.bright_black()
+                );
+            }
+            println!("{}", "Not executed (--dry-run).".bright_black());
+            self.add_message(MessageKind::ProposedCommand {
+                command: response.command,
+                message: response.message,
+                dangerous: response.dangerous,
+                category: response.category,
+            });
+            return Ok(());
+        }
+
+        let choice = if response.alternatives.is_empty() {
+            if !stream.command_shown {
+                println!("{} {}", "→".blue(), response.command);
+            }
+            let confirmed = if response.dangerous {
+                self.confirm_execution()?
+            } else if cached {
+                self.confirm("? Run cached command? [y/N]: ")?
+            } else {
+                true
+            };
+            confirmed.then(|| Alternative {
+                command: response.command.clone(),
+                description: response.message.clone(),
+                dangerous: response.dangerous,
+            })
+        } else {
+            match self.choose_command(&response)? {
+                Some(choice) if choice.dangerous && !self.confirm_execution()? => None,
+                choice => choice,
+            }
+        };
+
+        let Some(choice) = choice else {
+            self.add_message(MessageKind::ProposedCommand {
+                command: response.command,
+                message: response.message,
+                dangerous: response.dangerous,
+                category: response.category,
+            });
+            println!("Command execution cancelled.");
+            return Ok(());
+        };
+        self.editor.add_history_entry(&choice.command)?;
+        self.add_message(MessageKind::ProposedCommand {
+            command: choice.command.clone(),
+            message: choice.description,
+            dangerous: choice.dangerous,
+            category: response.category,
         });
+        self.run_with_fixes(choice.command).await
+    }
 
-        let pb = self.set_progress_message("Thinking...".yellow().to_string());
-        let request: GenerateContentRequest = serde_json::from_value(req_json)?;
-        let model = self.config.model.as_deref().unwrap_or("gemini-2.0-flash");
-        let response = self.client.generate_content(model, &request).await?;
-        pb.finish_and_clear();
-        if let Some(candidates) = response.candidates {
-            for candidate in &candidates {
-                for part in &candidate.content.parts {
-                    if let PartResponse::Text(json_str) = part {
-                        // Clean up the response string
-                        let clean_json = json_str
-                            .trim()
-                            .trim_start_matches("```json")
-                            .trim_end_matches("```")
-                            .trim();
-
-                        match serde_json::from_str::<GeminiResponse>(clean_json) {
-                            Ok(response) => {
-                                println!("{} {}", "🤖 →".green(), response.message.yellow());
-                                if response.command.is_empty() {
-                                    // Add model response to context
-                                    self.add_message("model", &format!("{}", response.message));
-                                    return Ok(());
-                                } else {
-                                    self.editor.add_history_entry(&response.command)?;
-                                }
-                                println!(
-                                    "{} {}",
-                                    "Category : ".green(),
-                                    response.category.yellow()
-                                );
-                                println!("{} {}", "→".blue(), response.command);
-                                self.add_message(
-                                    "model",
-                                    &format!(
-                                        "Command:{}, message:{}",
-                                        response.command, response.message
-                                    ),
-                                );
-
-                                if !response.dangerous || self.confirm_execution()? {
-                                    let pb = self.set_progress_message(
-                                        "Running command...".green().to_string(),
-                                    );
-                                    let output = self.execute_command(&response.command)?;
-                                    pb.finish_and_clear();
-                                    // Add command output to context
-                                    if !output.is_empty() {
-                                        self.add_message(
-                                            "model",
-                                            &format!("Command output:\n{}", output),
-                                        );
-                                    }
-                                } else {
-                                    println!("Command execution cancelled.");
-                                }
-                            }
-                            Err(e) => {
-                                eprintln!("Failed to parse response: {}", e);
-                                println!("Raw response: {}", clean_json);
-
-                                if cfg!(debug_assertions) {
-                                    println!(
-                                        "Debug: Response contains markdown block: {}",
-                                        json_str.contains("```")
-                                    );
-                                    println!("Debug: Cleaned JSON: {}", clean_json);
-                                }
-                            }
-                        }
+    /// Show the proposed command and its alternatives as a numbered menu and
+    /// return the picked, possibly edited, command with its explanation and
+    /// danger flag, or `None` if the user rejects them all
+    fn choose_command(
+        &mut self,
+        response: &GeminiResponse,
+    ) -> Result<Option<Alternative>, Box<dyn Error>> {
+        let mut options = vec![Alternative {
+            command: response.command.clone(),
+            description: response.message.clone(),
+            dangerous: response.dangerous,
+        }];
+        options.extend(response.alternatives.iter().cloned());
+
+        println!("{}", "🔀 Options:".blue());
+        for (i, option) in options.iter().enumerate() {
+            let warning = if option.dangerous { " ⚠️" } else { "" };
+            if i == 0 {
+                println!(
+                    "  1. {}{} {}",
+                    option.command,
+                    warning,
+                    "(recommended)".green()
+                );
+            } else {
+                println!("  {}. {}{}", i + 1, option.command, warning);
+                println!("     {}", option.description.bright_black());
+            }
+        }
+
+        if self.assume_yes {
+            println!("{}", "Picked 1 (--yes)".bright_black());
+            return Ok(Some(options.swap_remove(0)));
+        }
+
+        let prompt = format!(
+            "? Pick 1-{}, e<n> to edit, or n to reject [1]: ",
+            options.len()
+        );
+        loop {
+            let input = self.editor.readline(&prompt.blue().to_string())?;
+            let input = input.trim().to_lowercase();
+            if input == "n" || input == "no" {
+                return Ok(None);
+            }
+            let (edit, number) = match input.strip_prefix('e') {
+                Some(number) => (true, number.trim()),
+                None => (false, input.as_str()),
+            };
+            let index = if number.is_empty() {
+                1
+            } else {
+                match number.parse::<usize>() {
+                    Ok(index) if (1..=options.len()).contains(&index) => index,
+                    _ => {
+                        println!("{}", "Invalid choice.".red());
+                        continue;
                     }
                 }
+            };
+
+            let mut choice = options.swap_remove(index - 1);
+            if edit {
+                choice.command = self
+                    .editor
+                    .readline_with_initial("Edit: ", (&choice.command, ""))?
+                    .trim()
+                    .to_string();
+                if choice.command.is_empty() {
+                    return Ok(None);
+                }
             }
+            return Ok(Some(choice));
         }
-        Ok(())
+    }
+
+    /// Run a command and, when `auto_fix` is enabled, feed failures back to the
+    /// model and offer its corrected commands until one succeeds or the user stops
+    async fn run_with_fixes(&mut self, mut command: String) -> Result<(), Box<dyn Error>> {
+        if self.dry_run {
+            println!("{} {}", "Not executed (--dry-run):".bright_black(), command);
+            return Ok(());
+        }
+        let mut attempts = 0;
+        loop {
+            if let Some(forbidden) = self.project_instructions().forbidden_in(&command) {
+                println!(
+                    "{} {}",
+                    "⛔ Forbidden in this project:".red(),
+                    forbidden.yellow()
+                );
+                return Err(format!("Command uses forbidden command: {}", forbidden).into());
+            }
+
+            let pb = self.set_progress_message("Running command...".green().to_string());
+            let started = Instant::now();
+            let result = self.execute_command(&command);
+            pb.finish_and_clear();
+            let result = result.inspect_err(|e| {
+                log::error!("failed to run {:?}: {}", redact::secrets(&command), e)
+            })?;
+            log_execution(&result, started);
+            self.last_exit_code = result.exit_code;
+            self.record_history(&result);
+
+            self.add_execution_result(&result).await;
+            if result.success() {
+                self.record_step(&command);
+                return Ok(());
+            }
+
+            let exit_code = result.exit_code.unwrap_or(-1);
+            println!("{} {}", "⚠️ Command failed:".red(), command.yellow());
+            println!("{} {}", "Exit code:".red(), exit_code.to_string().yellow());
+            let error_message = format!("Command failed with exit code: {}", exit_code);
+
+            if !self.config.auto_fix || attempts >= self.config.max_fix_attempts {
+                self.explain_failure(&result).await;
+                return Err(error_message.into());
+            }
+            attempts += 1;
+
+            let fix_request = self
+                .config
+                .fix_prompt
+                .as_deref()
+                .unwrap_or(FIX_PROMPT)
+                .replace("{COMMAND}", &command)
+                .replace("{EXIT_CODE}", &exit_code.to_string())
+                .replace("{STDERR}", &self.truncated_stderr(&result.stderr));
+            self.add_message(MessageKind::SystemNote { text: fix_request });
+            self.compact_context().await;
+            // A failed command calls for the most capable model
+            let fix = self
+                .request_response(&self.model_for(&Route::Strong), None)
+                .await?;
+            println!("{} {}", "🤖 →".green(), fix.message.yellow());
+            if fix.command.is_empty() {
+                self.add_message(MessageKind::AssistantResponse { text: fix.message });
+                return Err(error_message.into());
+            }
+            self.add_message(MessageKind::ProposedCommand {
+                command: fix.command.clone(),
+                message: fix.message,
+                dangerous: fix.dangerous,
+                category: fix.category,
+            });
+            println!(
+                "{} {}",
+                format!(
+                    "🔧 Suggested fix ({}/{}):",
+                    attempts, self.config.max_fix_attempts
+                )
+                .green(),
+                fix.command
+            );
+
+            if !self.confirm("? Run suggested fix? [y/N]: ")?
+                || (fix.dangerous && !self.confirm_execution()?)
+            {
+                println!("Command execution cancelled.");
+                return Err(error_message.into());
+            }
+            self.editor.add_history_entry(&fix.command)?;
+            command = fix.command;
+        }
+    }
+
+    /// Project instructions for the current directory, ignoring unreadable files
+    fn project_instructions(&self) -> ProjectInstructions {
+        let cwd = std::env::current_dir().unwrap_or_default();
+        ProjectInstructions::discover(&cwd).unwrap_or_else(|e| {
+            eprintln!("{} {}", "⚠️ Ignoring project instructions:".yellow(), e);
+            ProjectInstructions::default()
+        })
+    }
+
+    fn confirm(&mut self, prompt: &str) -> io::Result<bool> {
+        if self.assume_yes {
+            println!("{}{}", prompt.red(), "y (--yes)".bright_black());
+            return Ok(true);
+        }
+        self.read_yes(prompt)
+    }
+
+    fn read_yes(&mut self, prompt: &str) -> io::Result<bool> {
+        let input = self
+            .editor
+            .readline(&prompt.red().to_string())
+            .map_err(io::Error::other)?;
+        Ok(input.trim().to_lowercase() == "y")
     }
 
     fn confirm_execution(&mut self) -> io::Result<bool> {
         let _input = self
             .editor
             .readline(&("? Execute? [y/N]: ".red().to_string()))
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            .map_err(io::Error::other)?;
         print!("{}️", "⚠️".red());
         if _input.trim() == "N" || _input.trim() == "n" {
             return Ok(false);
         }
-        let _input = self
-            .editor
-            .readline(
-                &(" Execute potentially dangerous command? [y/N]: "
-                    .red()
-                    .to_string()),
-            )
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-        Ok(_input.trim().to_lowercase() == "y")
+        // Dangerous commands always need a typed answer, even with --yes
+        self.read_yes(" Execute potentially dangerous command? [y/N]: ")
     }
 
-    fn execute_command(&self, command: &str) -> Result<String, Box<dyn Error>> {
+    fn execute_command(&self, command: &str) -> Result<CommandResult, Box<dyn Error>> {
         #[cfg(target_os = "windows")]
         let (program, args) = ("cmd", vec!["/C", command]);
 
         #[cfg(not(target_os = "windows"))]
         let (program, args) = ("sh", vec!["-c", command]);
 
-        let output = Command::new(program).args(args).output()?;
+        let output = match &self.piped_input {
+            Some(input) => {
+                let mut child = Command::new(program)
+                    .args(args)
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .spawn()?;
+                // Write from another thread so a command that doesn't read all
+                // of its input can't block on a full output pipe
+                let mut stdin = child.stdin.take().ok_or("Failed to open command stdin")?;
+                let input = input.clone();
+                let writer = std::thread::spawn(move || {
+                    // Commands may exit without reading their input
+                    let _ = stdin.write_all(&input);
+                });
+                let output = child.wait_with_output()?;
+                let _ = writer.join();
+                output
+            }
+            None => Command::new(program).args(args).output()?,
+        };
 
-        io::stdout().write_all(&output.stdout)?;
+        if !self.quiet {
+            io::stdout().write_all(&output.stdout)?;
+            io::stderr().write_all(&output.stderr)?;
+        }
 
-        if !output.status.success() {
-            println!("{} {}", "⚠️ Command failed:".red(), command.yellow());
-            println!(
-                "{} {}",
-                "Exit code:".red(),
-                output.status.code().unwrap_or(-1).to_string().yellow()
-            );
-            let error_message = format!(
-                "Command failed with exit code: {}",
-                output.status.code().unwrap_or(-1)
-            );
+        Ok(CommandResult {
+            command: command.to_string(),
+            exit_code: output.status.code(),
+            stdout: output::decode(&output.stdout),
+            stderr: output::decode(&output.stderr),
+        })
+    }
 
-            // Use a cloned progress bar for AI analysis in async
-            let pb = self.set_progress_message("Requesting AI analysis...".blue().to_string());
+    /// Ask Gemini why a command failed, render the analysis and keep it in the context
+    async fn explain_failure(&mut self, result: &CommandResult) -> Option<FailureAnalysis> {
+        let pb = self.set_progress_message("Requesting AI analysis...".blue().to_string());
+        let analysis = self.request_failure_analysis(result).await;
+        pb.finish_and_clear();
 
-            let command_clone = command.to_string();
-            let error_message_clone = error_message.clone();
-            let client_clone = GeminiClient::new(self.config.api_key.clone());
-            let model = self
-                .config
-                .model
-                .clone()
-                .unwrap_or_else(|| "gemini-2.0-flash".to_string());
-            tokio::spawn(async move {
-                let prompt = EXPLANATION_PROMPT
-                    .replace("{COMMAND}", &command_clone)
-                    .replace("{ERROR}", &error_message_clone);
-
-                let req_json = json!({"contents": [{
-                        "parts": [{
-                            "text": prompt
-                        }],
-                        "role": "user"
-                    }],
-                    "tools": []
-                });
+        let analysis = match analysis {
+            Ok(Some(analysis)) => analysis,
+            Ok(None) => {
+                println!("{}", "No AI explanation available.".red());
+                return None;
+            }
+            Err(_) => {
+                println!("{}", "Failed to get AI explanation.".red());
+                return None;
+            }
+        };
 
-                let request: GenerateContentRequest = serde_json::from_value(req_json).unwrap();
-                if let Ok(response) = client_clone.generate_content(&model, &request).await {
-                    if let Some(candidates) = response.candidates {
-                        for candidate in &candidates {
-                            for part in &candidate.content.parts {
-                                if let PartResponse::Text(explanation) = part {
-                                    pb.finish_and_clear();
-                                    println!(
-                                        "{} {}",
-                                        "🤖 AI Explanation:".green(),
-                                        explanation.yellow()
-                                    );
-                                }
-                            }
-                        }
-                    } else {
-                        pb.finish_and_clear();
-                        println!("{}", "No AI explanation available.".red());
-                    }
-                } else {
-                    pb.finish_and_clear();
-                    println!("{}", "Failed to get AI explanation.".red());
+        println!(
+            "{} {}",
+            "🤖 AI Explanation:".green(),
+            analysis.cause.yellow()
+        );
+        for suggestion in &analysis.suggestions {
+            println!("  {} {}", "•".green(), suggestion);
+        }
+        self.add_message(MessageKind::AssistantResponse {
+            text: format!(
+                "Failure analysis for `{}`: {} Suggestions: {}",
+                result.command,
+                analysis.cause,
+                analysis.suggestions.join("; ")
+            ),
+        });
+        Some(analysis)
+    }
+
+    async fn request_failure_analysis(
+        &self,
+        result: &CommandResult,
+    ) -> Result<Option<FailureAnalysis>, Box<dyn Error>> {
+        let error_message = format!(
+            "Command failed with exit code: {}\n{}",
+            result.exit_code.unwrap_or(-1),
+            self.truncated_stderr(&result.stderr)
+        );
+        let prompt = self
+            .config
+            .explanation_prompt
+            .as_deref()
+            .unwrap_or(EXPLANATION_PROMPT)
+            .replace("{COMMAND}", &result.command)
+            .replace("{ERROR}", &error_message);
+
+        let schema = json!({
+            "type": "object",
+            "required": ["cause", "suggestions"],
+            "properties": {
+                "cause": {
+                    "type": "string",
+                    "description": "Short explanation of why the command failed"
+                },
+                "suggestions": {
+                    "type": "array",
+                    "description": "One or two concise ways to fix the failure",
+                    "items": { "type": "string" }
                 }
-            });
+            }
+        });
+        Ok(self
+            .generate_once(&prompt, Some(schema))
+            .await?
+            .map(|text| {
+                serde_json::from_str::<FailureAnalysis>(&text).unwrap_or(FailureAnalysis {
+                    cause: text,
+                    suggestions: Vec::new(),
+                })
+            }))
+    }
 
-            return Err(error_message.into());
+    /// Store the outcome of a command in the context, truncating or summarizing
+    /// large outputs so they don't crowd out the rest of the conversation
+    async fn add_execution_result(&mut self, result: &CommandResult) {
+        let truncated = output::truncate(
+            &result.stdout,
+            self.config.output_head_lines,
+            self.config.output_tail_lines,
+            self.config.max_output_bytes,
+        );
+        let stdout = if !truncated.truncated {
+            truncated.text
+        } else {
+            let summary = if self.config.summarize_large_output {
+                let pb = self.set_progress_message("Summarizing output...".blue().to_string());
+                let summary = self.summarize_output(&result.command, &result.stdout).await;
+                pb.finish_and_clear();
+                summary.ok()
+            } else {
+                None
+            };
+            match summary {
+                Some(summary) => {
+                    format!("(summary of {} lines)\n{}", truncated.total_lines, summary)
+                }
+                None => format!(
+                    "({} lines, truncated)\n{}",
+                    truncated.total_lines, truncated.text
+                ),
+            }
+        };
+        self.add_message(MessageKind::ExecutionResult {
+            command: result.command.clone(),
+            exit_code: result.exit_code,
+            stdout,
+            stderr: self.truncated_stderr(&result.stderr),
+        });
+    }
+
+    async fn summarize_output(
+        &self,
+        command: &str,
+        stdout: &str,
+    ) -> Result<String, Box<dyn Error>> {
+        let capped = output::truncate(stdout, 1000, 1000, MAX_SUMMARIZED_OUTPUT_BYTES);
+        let prompt = OUTPUT_SUMMARY_PROMPT
+            .replace("{COMMAND}", command)
+            .replace("{OUTPUT}", &capped.text);
+        let schema = json!({
+            "type": "object",
+            "required": ["summary", "highlights"],
+            "properties": {
+                "summary": {
+                    "type": "string",
+                    "description": "One or two sentences describing the output"
+                },
+                "highlights": {
+                    "type": "array",
+                    "description": "Key facts, errors, counts or values from the output",
+                    "items": { "type": "string" }
+                }
+            }
+        });
+        let text = self
+            .generate_once(&prompt, Some(schema))
+            .await?
+            .ok_or("Empty summary")?;
+        let summary: OutputSummary = serde_json::from_str(&text)?;
+        let mut rendered = summary.summary;
+        for highlight in summary.highlights {
+            rendered.push_str(&format!("\n- {}", highlight));
         }
-        Ok(String::from_utf8(output.stdout)?)
+        Ok(rendered)
+    }
+
+    /// Truncate stderr before it is sent to the model
+    fn truncated_stderr(&self, stderr: &str) -> String {
+        output::truncate(
+            stderr.trim(),
+            self.config.output_head_lines,
+            self.config.output_tail_lines,
+            self.config.max_output_bytes,
+        )
+        .text
     }
 
     fn clear_context(&mut self) -> Result<(), Box<dyn Error>> {
@@ -487,7 +2154,17 @@ impl NexSh {
         println!("  - Type any command to execute it.");
         println!("  - Use 'init' to set up your API key.");
         println!("  - Use 'clear' to clear conversation context.");
-        println!("  - Type 'models' to list and select available Gemini models interactively.");
+        println!("  - Type 'models' to list and select available Gemini models interactively ('models refresh' to refetch the list).");
+        println!("  - Use 'session [list|new|switch|delete|rename]' to manage named sessions.");
+        println!("  - Use 'export [md|json|sh] [file]' to export the current session.");
+        println!("  - Use 'import <file> [session]' to start a session from an export.");
+        println!("  - Use 'record start <name>' / 'record stop' to record a runbook.");
+        println!("  - Use 'run-book <name> [--adapt]' to replay a recorded runbook.");
+        println!(
+            "  - Use 'history [search]' to find past requests, 'history run <id>' to rerun one."
+        );
+        println!("  - Use 'usage' to see token usage and estimated cost.");
+        println!("  - Set 'auto_fix' in the config to get corrected commands when one fails.");
         Ok(())
     }
 
@@ -502,12 +2179,18 @@ impl NexSh {
                     .split(std::path::MAIN_SEPARATOR)
                     .map(|s| s.bright_cyan().to_string())
                     .collect::<Vec<_>>()
-                    .join(&format!(
-                        "{}",
-                        std::path::MAIN_SEPARATOR.to_string().bright_black()
-                    )),
-                "NexSh →".green()
+                    .join(&std::path::MAIN_SEPARATOR_STR.bright_black().to_string()),
+                if self.session == session::DEFAULT_SESSION {
+                    "NexSh →".green()
+                } else {
+                    format!("NexSh [{}] →", self.session).green()
+                }
             );
+            let prompt = if self.recording.is_some() {
+                format!("{} {}", "⏺".red(), prompt)
+            } else {
+                prompt
+            };
             match self.editor.readline(&prompt) {
                 Ok(line) => {
                     let input = line.trim();
@@ -515,33 +2198,9 @@ impl NexSh {
                         continue;
                     }
 
-                    if input == "models" {
-                        let models = list_available_models();
-                        println!("Available Gemini models:");
-                        for (i, m) in models.iter().enumerate() {
-                            println!("  {}. {}", i + 1, m);
-                        }
-                        let input = self
-                            .editor
-                            .readline("Select model by number or name (Enter to cancel): ")
-                            .unwrap_or_default();
-                        let model = input.trim();
-                        if !model.is_empty() {
-                            let selected = if let Ok(idx) = model.parse::<usize>() {
-                                models
-                                    .get(idx.saturating_sub(1))
-                                    .copied()
-                                    .unwrap_or(models[0])
-                            } else {
-                                models
-                                    .iter()
-                                    .find(|m| m.starts_with(model))
-                                    .copied()
-                                    .unwrap_or(models[0])
-                            };
-                            if let Err(e) = self.set_model(selected) {
-                                eprintln!("{} {}", "error:".red(), e);
-                            }
+                    if input == "models" || input == "models refresh" {
+                        if let Err(e) = self.models_command(input == "models refresh").await {
+                            eprintln!("{} {}", "error:".red(), e);
                         }
                         continue;
                     }
@@ -549,8 +2208,46 @@ impl NexSh {
                     match input {
                         "exit" | "quit" => break,
                         "clear" => self.clear_context()?,
-                        "init" => self.initialize()?,
+                        "init" => self.initialize().await?,
                         "help" => self.print_help()?,
+                        "usage" => self.usage_command(),
+                        _ if input == "export" || input.starts_with("export ") => {
+                            if let Err(e) = self.export_command(&input["export".len()..]) {
+                                eprintln!("{} {}", "error:".red(), e);
+                            }
+                        }
+                        _ if input.starts_with("import ") => {
+                            if let Err(e) = self.import_command(&input["import".len()..]) {
+                                eprintln!("{} {}", "error:".red(), e);
+                            }
+                        }
+                        _ if input == "history" || input.starts_with("history ") => {
+                            if let Err(e) = self.history_command(&input["history".len()..]).await {
+                                eprintln!("{} {}", "error:".red(), e);
+                            }
+                        }
+                        _ if input == "record" || input.starts_with("record ") => {
+                            if let Err(e) = self.record_command(&input["record".len()..]) {
+                                eprintln!("{} {}", "error:".red(), e);
+                            }
+                        }
+                        _ if input.starts_with("run-book ") => {
+                            let args: Vec<&str> = input.split_whitespace().skip(1).collect();
+                            let adapt = args.contains(&"--adapt");
+                            match args.iter().find(|arg| !arg.starts_with("--")) {
+                                Some(name) => {
+                                    if let Err(e) = self.replay_runbook(name, adapt).await {
+                                        eprintln!("{} {}", "error:".red(), e);
+                                    }
+                                }
+                                None => println!("Usage: run-book <name> [--adapt]"),
+                            }
+                        }
+                        _ if input == "session" || input.starts_with("session ") => {
+                            if let Err(e) = self.session_command(&input["session".len()..]) {
+                                eprintln!("{} {}", "error:".red(), e);
+                            }
+                        }
                         _ => {
                             if let Err(e) = self.process_command(input).await {
                                 eprintln!("{} {}", "error:".red(), e);
@@ -576,3 +2273,67 @@ impl NexSh {
         Ok(())
     }
 }
+
+/// Log a Gemini request with its latency and outcome; the redacted body is only
+/// logged at `debug` level
+fn log_request(
+    model: &str,
+    request: &serde_json::Value,
+    streamed: bool,
+    result: &Result<GenerateContentResponse, ApiError>,
+    started: Instant,
+) {
+    let latency = started.elapsed().as_millis();
+    if logging::enabled(log::Level::Debug) {
+        // The tool declarations never change, their names are enough
+        let mut body = logging::redact(request);
+        if let Some(tools) = body.get_mut("tools") {
+            *tools = json!(request["tools"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|tool| tool["function_declarations"].as_array())
+                .flatten()
+                .filter_map(|function| function["name"].as_str())
+                .collect::<Vec<_>>());
+        }
+        log::debug!("request model={} body={}", model, body);
+    }
+    match result {
+        Ok(response) => {
+            let (prompt_tokens, response_tokens) = response
+                .usage_metadata
+                .as_ref()
+                .map(|usage| (usage.prompt_token_count, usage.candidates_token_count))
+                .unwrap_or_default();
+            log::info!(
+                "request model={} streamed={} latency_ms={} prompt_tokens={} response_tokens={}",
+                model,
+                streamed,
+                latency,
+                prompt_tokens,
+                response_tokens
+            );
+        }
+        Err(e) => log::warn!(
+            "request failed model={} streamed={} latency_ms={} error={:?}",
+            model,
+            streamed,
+            latency,
+            e.to_string()
+        ),
+    }
+}
+
+fn log_execution(result: &CommandResult, started: Instant) {
+    log::info!(
+        "executed command={:?} exit_code={} duration_ms={} stdout_bytes={} stderr_bytes={}",
+        redact::secrets(&result.command),
+        result
+            .exit_code
+            .map_or_else(|| "none".to_string(), |code| code.to_string()),
+        started.elapsed().as_millis(),
+        result.stdout.len(),
+        result.stderr.len()
+    );
+}
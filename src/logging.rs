@@ -0,0 +1,155 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde_json::{json, Value};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+/// Environment variable holding the log level: off, error, warn, info, debug or trace
+pub const LOG_ENV: &str = "NEXSH_LOG";
+
+/// Size at which the log file is rotated
+const MAX_LOG_BYTES: u64 = 1024 * 1024;
+
+/// Rotated files kept next to the current one, `nexsh.log.1` being the newest
+const KEPT_LOGS: usize = 3;
+
+/// Strings in logged request bodies longer than this are replaced by their length
+const MAX_LOGGED_STRING: usize = 80;
+
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+/// Writes log records as JSON lines to `nexsh.log` in the config directory and,
+/// in verbose mode, echoes them to stderr
+struct FileLogger {
+    path: PathBuf,
+    file: Mutex<Option<File>>,
+}
+
+impl FileLogger {
+    fn open(path: &Path) -> Option<File> {
+        OpenOptions::new().create(true).append(true).open(path).ok()
+    }
+
+    /// Shift `nexsh.log` to `nexsh.log.1`, `.1` to `.2` and so on, dropping the oldest
+    fn rotate(&self) {
+        let rotated = |n: usize| PathBuf::from(format!("{}.{}", self.path.display(), n));
+        for n in (1..KEPT_LOGS).rev() {
+            let _ = fs::rename(rotated(n), rotated(n + 1));
+        }
+        let _ = fs::rename(&self.path, rotated(1));
+    }
+}
+
+impl Log for FileLogger {
+    /// Only records of nexsh itself, dependencies like reqwest log too much
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level() && metadata.target().starts_with("nexsh")
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let message = record.args().to_string();
+        if VERBOSE.load(Ordering::Relaxed) {
+            eprintln!("[{}] {}", record.level(), message);
+        }
+
+        let line = json!({
+            "ts": chrono::Local::now().to_rfc3339(),
+            "level": record.level().as_str(),
+            "target": record.target(),
+            "msg": message,
+        });
+        let Ok(mut file) = self.file.lock() else {
+            return;
+        };
+        let full = file
+            .as_ref()
+            .and_then(|file| file.metadata().ok())
+            .is_some_and(|metadata| metadata.len() >= MAX_LOG_BYTES);
+        if full {
+            self.rotate();
+            *file = Self::open(&self.path);
+        }
+        if let Some(file) = file.as_mut() {
+            let _ = writeln!(file, "{}", line);
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            if let Some(file) = file.as_mut() {
+                let _ = file.flush();
+            }
+        }
+    }
+}
+
+/// Level from `NEXSH_LOG`, `info` when unset or invalid; verbose mode raises it
+/// to at least `debug`
+fn level() -> LevelFilter {
+    let level = std::env::var(LOG_ENV)
+        .ok()
+        .and_then(|value| LevelFilter::from_str(value.trim()).ok())
+        .unwrap_or(LevelFilter::Info);
+    if VERBOSE.load(Ordering::Relaxed) {
+        level.max(LevelFilter::Debug)
+    } else {
+        level
+    }
+}
+
+/// Start logging to `nexsh.log` in `config_dir`. Only the first call installs
+/// the logger, later ones just refresh the level
+pub fn init(config_dir: &Path) {
+    let path = config_dir.join("nexsh.log");
+    let logger = FileLogger {
+        file: Mutex::new(FileLogger::open(&path)),
+        path,
+    };
+    let _ = log::set_boxed_logger(Box::new(logger));
+    log::set_max_level(level());
+}
+
+/// Log at `debug` level or finer and echo records to stderr
+pub fn enable_verbose() {
+    VERBOSE.store(true, Ordering::Relaxed);
+    log::set_max_level(level());
+}
+
+/// Whether records at `level` are written, to skip building costly messages
+pub fn enabled(level: Level) -> bool {
+    level <= log::max_level()
+}
+
+/// Copy of a request body safe to put in a log: message texts, which may hold
+/// secrets typed by the user or found in command output, and other long strings
+/// are replaced by their length
+pub fn redact(value: &Value) -> Value {
+    match value {
+        Value::String(text) if text.chars().count() > MAX_LOGGED_STRING => hidden(text),
+        Value::Array(items) => Value::Array(items.iter().map(redact).collect()),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, value)| match value {
+                    Value::String(text) if key == "text" => (key.clone(), hidden(text)),
+                    value => (key.clone(), redact(value)),
+                })
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+fn hidden(text: &str) -> Value {
+    Value::String(format!("<{} chars>", text.chars().count()))
+}
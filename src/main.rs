@@ -1,36 +1,343 @@
-use clap::Parser;
-use nexsh::NexSh;
-use std::error::Error;
+use clap::{error::ErrorKind, Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use nexsh::{export::ExportFormat, NexSh};
+use std::{
+    error::Error,
+    io::{self, Write},
+    path::PathBuf,
+};
 mod header;
-pub mod prompt;
-pub mod types;
 
 #[derive(Parser, Debug)]
 #[command(
     name = "nexsh",
-    version = "0.2.0",
-    about = "AI-powered smart shell using Google Gemini"
+    version,
+    about = "AI-powered smart shell using Google Gemini",
+    long_about = "AI-powered smart shell using Google Gemini.\n\nRun without a subcommand to start the interactive shell."
 )]
-struct Args {
-    /// Execute single command and exit
-    #[arg(short, long)]
+struct Cli {
+    /// Same as `exec`, kept for compatibility
+    #[arg(short, long, hide = true)]
     execute: Option<String>,
+
+    /// `--output` of `exec`, for `-e`
+    #[arg(long, value_enum, hide = true, requires = "execute")]
+    output: Option<OutputFormat>,
+
+    /// `--raw` of `exec`, for `-e`
+    #[arg(long, hide = true, requires = "execute", conflicts_with = "output")]
+    raw: bool,
+
+    /// Use this model instead of the configured and routed ones
+    #[arg(short, long, global = true)]
+    model: Option<String>,
+
+    /// Use the named conversation session
+    #[arg(short, long, global = true)]
+    session: Option<String>,
+
+    /// Answer confirmations with yes; dangerous commands still ask
+    #[arg(short, long, global = true)]
+    yes: bool,
+
+    /// Show the commands that would run without running them
+    #[arg(long, global = true, alias = "no-exec")]
+    dry_run: bool,
+
+    /// Don't print the banner
+    #[arg(long, global = true)]
+    no_header: bool,
+
+    /// Always ask the model instead of reusing cached responses
+    #[arg(long, global = true)]
+    no_cache: bool,
+
+    /// Log debug details to stderr and the log file (level also set by NEXSH_LOG)
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Args, Debug)]
+struct Request {
+    /// What you want, in natural language
+    #[arg(required = true, num_args = 1..)]
+    request: Vec<String>,
+
+    /// Print the result as text or as a single JSON document
+    #[arg(long, value_enum, default_value_t)]
+    output: OutputFormat,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Set up the API key, model and other settings
+    Init,
+    /// Ask a question or for a command, without running anything
+    Ask(Request),
+    /// Turn a request into a command and run it
+    Exec {
+        #[command(flatten)]
+        request: Request,
+
+        /// Work as a filter: print only the stdout of the command run on the piped input
+        #[arg(long, conflicts_with = "output")]
+        raw: bool,
+    },
+    /// List the available models and pick the default one
+    Models {
+        /// Fetch the list again instead of using the cached one
+        #[arg(long)]
+        refresh: bool,
+    },
+    /// Show or change settings
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// List or search past requests, or run one again
+    History {
+        /// Words to search for
+        query: Vec<String>,
+
+        /// Run the entry with this id again
+        #[arg(long, value_name = "ID", conflicts_with = "query")]
+        run: Option<usize>,
+    },
+    /// List and manage conversation sessions
+    Sessions {
+        #[command(subcommand)]
+        action: Option<SessionAction>,
+    },
+    /// Export the current session as Markdown, JSON or a shell script
+    Export {
+        #[arg(short, long, value_enum, default_value_t = ExportFormat::Markdown)]
+        format: ExportFormat,
+
+        /// Output file, generated in the current directory by default
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Start a new session from a JSON or shell script export
+    Import {
+        path: PathBuf,
+
+        /// Name of the new session, defaults to the file name
+        #[arg(short, long)]
+        name: Option<String>,
+    },
+    /// Replay a recorded runbook step by step
+    #[command(name = "run-book")]
+    RunBook {
+        name: String,
+
+        /// Ask the AI to adapt each command to the current OS and directory
+        #[arg(short, long)]
+        adapt: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigAction {
+    /// Print a setting, or all settings
+    Get { key: Option<String> },
+    /// Change a setting; numbers, booleans and lists are given as JSON
+    Set { key: String, value: String },
+}
+
+#[derive(Subcommand, Debug)]
+enum SessionAction {
+    /// List the sessions
+    List,
+    /// Create a session and make it the active one
+    New { name: String },
+    /// Make a session the active one
+    Switch { name: String },
+    /// Delete a session
+    Delete { name: String },
+    /// Rename a session
+    Rename { from: String, to: String },
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    header::print_header();
+    let cli = Cli::parse();
+    if cli.verbose {
+        nexsh::logging::enable_verbose();
+    }
+
+    let command = match (cli.command, cli.execute) {
+        (None, Some(request)) => Some(Commands::Exec {
+            request: Request {
+                request: vec![request],
+                output: cli.output.unwrap_or_default(),
+            },
+            raw: cli.raw,
+        }),
+        (Some(_), Some(_)) => Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "the argument '--execute' cannot be used with a subcommand",
+            )
+            .exit(),
+        (command, None) => command,
+    };
+
+    // Machine-readable output and management commands never show the banner
+    let show_header = !cli.no_header
+        && match &command {
+            None | Some(Commands::Init) | Some(Commands::RunBook { .. }) => true,
+            Some(Commands::Ask(request)) => request.output == OutputFormat::Text,
+            Some(Commands::Exec { request, raw }) => request.output == OutputFormat::Text && !raw,
+            Some(_) => false,
+        };
+    if show_header {
+        header::print_header();
+    }
 
-    let args = Args::parse();
     let mut shell = NexSh::new()?;
+    if let Some(session) = &cli.session {
+        shell.switch_session(session)?;
+    }
+    if let Some(model) = &cli.model {
+        shell.use_model(model);
+    }
+    if cli.yes {
+        shell.assume_yes();
+    }
+    if cli.dry_run {
+        shell.enable_dry_run();
+    }
+    if cli.no_cache {
+        shell.disable_cache();
+    }
 
-    if let Some(cmd) = args.execute {
-        if cmd == "--help" || cmd == "-h" {
-            shell.print_help()?;
-            return Ok(());
+    let result = match command {
+        None => shell.run().await,
+        Some(Commands::Init) => shell.initialize().await,
+        Some(Commands::Ask(request)) => {
+            shell.enable_dry_run();
+            let code = run_request(&mut shell, request, false).await;
+            std::process::exit(code);
+        }
+        Some(Commands::Exec { request, raw }) => {
+            let code = run_request(&mut shell, request, raw).await;
+            std::process::exit(code);
+        }
+        Some(Commands::Models { refresh }) => shell.models_command(refresh).await,
+        Some(Commands::Config { action }) => match action {
+            ConfigAction::Get { key } => shell.config_get(key.as_deref()),
+            ConfigAction::Set { key, value } => shell.config_set(&key, &value),
+        },
+        Some(Commands::History { query, run }) => {
+            let args = match run {
+                Some(id) => format!("run {}", id),
+                None => query.join(" "),
+            };
+            shell.history_command(&args).await
+        }
+        Some(Commands::Sessions { action }) => {
+            let args = match action.unwrap_or(SessionAction::List) {
+                SessionAction::List => "list".to_string(),
+                SessionAction::New { name } => format!("new {}", name),
+                SessionAction::Switch { name } => format!("switch {}", name),
+                SessionAction::Delete { name } => format!("delete {}", name),
+                SessionAction::Rename { from, to } => format!("rename {} {}", from, to),
+            };
+            shell.session_command(&args)
         }
-        return shell.process_command(&cmd).await;
+        Some(Commands::Export { format, output }) => shell
+            .export_session(format, output.as_deref())
+            .map(|path| println!("✅ Exported session to {}", path.display())),
+        Some(Commands::Import { path, name }) => shell
+            .import_session(&path, name.as_deref())
+            .map(|name| println!("✅ Imported into session: {}", name)),
+        Some(Commands::RunBook { name, adapt }) => shell.replay_runbook(&name, adapt).await,
+    };
+
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        std::process::exit(failure_code(&shell));
     }
+    Ok(())
+}
 
-    shell.run().await
+/// Exit code after an error: the failed command's own code, or 1
+fn failure_code(shell: &NexSh) -> i32 {
+    shell
+        .last_exit_code()
+        .filter(|code| *code != 0)
+        .unwrap_or(1)
+}
+
+/// Handle `ask` and `exec`, returning the process exit code: the executed
+/// command's exit code, 0 when nothing ran and 1 on errors
+async fn run_request(shell: &mut NexSh, request: Request, raw: bool) -> i32 {
+    let input = request.request.join(" ");
+    if let Err(e) = shell.read_piped_input() {
+        eprintln!("error: {}", e);
+        return 1;
+    }
+
+    if request.output == OutputFormat::Text && !raw {
+        return match shell.process_command(&input).await {
+            Ok(()) => 0,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                failure_code(shell)
+            }
+        };
+    }
+
+    shell.enable_quiet();
+    let report = match shell.report(&input).await {
+        Ok(report) => report,
+        Err(e) if raw => {
+            eprintln!("error: {}", e);
+            return 1;
+        }
+        Err(e) => {
+            println!("{}", serde_json::json!({ "error": e.to_string() }));
+            return 1;
+        }
+    };
+    let code = if report.executed {
+        report.exit_code.unwrap_or(1)
+    } else {
+        0
+    };
+
+    if !raw {
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                eprintln!("error: {}", e);
+                return 1;
+            }
+        }
+        return code;
+    }
+
+    if !report.executed {
+        match (&report.command, &report.skipped) {
+            (Some(command), Some(reason)) => {
+                eprintln!("error: command not run ({}): {}", reason, command);
+                return 1;
+            }
+            _ => println!("{}", report.message),
+        }
+        return 0;
+    }
+    print!("{}", report.stdout.unwrap_or_default());
+    eprint!("{}", report.stderr.unwrap_or_default());
+    let _ = io::stdout().flush();
+    code
 }
@@ -0,0 +1,97 @@
+/// Bytes inspected when sniffing for binary content
+const SNIFF_BYTES: usize = 8 * 1024;
+
+/// Decode command output, replacing binary data with a short placeholder and
+/// invalid UTF-8 sequences with U+FFFD
+pub fn decode(bytes: &[u8]) -> String {
+    if is_binary(bytes) {
+        format!("[binary output: {} bytes]", bytes.len())
+    } else {
+        String::from_utf8_lossy(bytes).into_owned()
+    }
+}
+
+/// Treat output as binary if it contains NUL bytes or is mostly not valid UTF-8
+pub fn is_binary(bytes: &[u8]) -> bool {
+    let sample = &bytes[..bytes.len().min(SNIFF_BYTES)];
+    if sample.contains(&0) {
+        return true;
+    }
+    let invalid = String::from_utf8_lossy(sample)
+        .chars()
+        .filter(|&c| c == char::REPLACEMENT_CHARACTER)
+        .count();
+    invalid * 10 > sample.len()
+}
+
+/// Truncated view of a command output
+pub struct Truncated {
+    pub text: String,
+    pub total_lines: usize,
+    pub truncated: bool,
+}
+
+/// Keep the first `head_lines` and last `tail_lines` lines, then cap the result
+/// at `max_bytes`
+pub fn truncate(text: &str, head_lines: usize, tail_lines: usize, max_bytes: usize) -> Truncated {
+    let lines: Vec<&str> = text.lines().collect();
+    let total_lines = lines.len();
+    let mut truncated = false;
+
+    let mut result = if total_lines > head_lines + tail_lines {
+        truncated = true;
+        format!(
+            "{}\n… {} lines omitted …\n{}",
+            lines[..head_lines].join("\n"),
+            total_lines - head_lines - tail_lines,
+            lines[total_lines - tail_lines..].join("\n")
+        )
+    } else {
+        text.to_string()
+    };
+
+    if result.len() > max_bytes {
+        truncated = true;
+        let mut end = max_bytes;
+        while !result.is_char_boundary(end) {
+            end -= 1;
+        }
+        result.truncate(end);
+        result.push_str("\n… (truncated)");
+    }
+
+    Truncated {
+        text: result,
+        total_lines,
+        truncated,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_short_output() {
+        let result = truncate("a\nb\nc", 2, 1, 100);
+        assert_eq!(result.text, "a\nb\nc");
+        assert_eq!(result.total_lines, 3);
+        assert!(!result.truncated);
+    }
+
+    #[test]
+    fn keeps_head_and_tail_lines() {
+        let text: Vec<String> = (1..=10).map(|i| i.to_string()).collect();
+        let result = truncate(&text.join("\n"), 2, 1, 1000);
+        assert_eq!(result.text, "1\n2\n… 7 lines omitted …\n10");
+        assert_eq!(result.total_lines, 10);
+        assert!(result.truncated);
+    }
+
+    #[test]
+    fn caps_bytes_on_a_char_boundary() {
+        let result = truncate("ééééé", 10, 10, 3);
+        assert_eq!(result.text, "é\n… (truncated)");
+        assert!(result.truncated);
+    }
+}
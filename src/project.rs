@@ -0,0 +1,204 @@
+use serde::Deserialize;
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub const PROJECT_CONFIG_FILE: &str = ".nexsh.toml";
+pub const PROJECT_NOTES_FILE: &str = "NEXSH.md";
+
+/// Maximum size of a `NEXSH.md` file included in the prompt
+const MAX_NOTES_BYTES: usize = 8 * 1024;
+
+/// Contents of a `.nexsh.toml` project file
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ProjectConfig {
+    pub instructions: Option<String>,
+    pub preferred_tools: Vec<String>,
+    pub forbidden_commands: Vec<String>,
+}
+
+/// Instructions collected from the current directory and its ancestors
+#[derive(Debug, Default)]
+pub struct ProjectInstructions {
+    pub sources: Vec<PathBuf>,
+    pub instructions: Vec<String>,
+    pub preferred_tools: Vec<String>,
+    pub forbidden_commands: Vec<String>,
+}
+
+impl ProjectInstructions {
+    /// Collect project files from `start` up to the filesystem root; files closer
+    /// to `start` come last so their instructions take precedence
+    pub fn discover(start: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut dirs: Vec<&Path> = start.ancestors().collect();
+        dirs.reverse();
+
+        let mut project = Self::default();
+        for dir in dirs {
+            let config_file = dir.join(PROJECT_CONFIG_FILE);
+            if config_file.is_file() {
+                let content = fs::read_to_string(&config_file)?;
+                let config: ProjectConfig = toml::from_str(&content)
+                    .map_err(|e| format!("{}: {}", config_file.display(), e))?;
+                project.instructions.extend(config.instructions);
+                project.preferred_tools.extend(config.preferred_tools);
+                project.forbidden_commands.extend(config.forbidden_commands);
+                project.sources.push(config_file);
+            }
+
+            let notes_file = dir.join(PROJECT_NOTES_FILE);
+            if notes_file.is_file() {
+                let mut notes = fs::read_to_string(&notes_file)?;
+                if notes.len() > MAX_NOTES_BYTES {
+                    let mut end = MAX_NOTES_BYTES;
+                    while !notes.is_char_boundary(end) {
+                        end -= 1;
+                    }
+                    notes.truncate(end);
+                }
+                project.instructions.push(notes);
+                project.sources.push(notes_file);
+            }
+        }
+        Ok(project)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    /// Render as a section appended to the system prompt
+    pub fn render(&self) -> String {
+        let mut prompt = String::from("\nPROJECT INSTRUCTIONS:\n");
+        for instructions in &self.instructions {
+            prompt.push_str(instructions.trim());
+            prompt.push('\n');
+        }
+        if !self.preferred_tools.is_empty() {
+            prompt.push_str(&format!(
+                "- Prefer these tools: {}\n",
+                self.preferred_tools.join(", ")
+            ));
+        }
+        if !self.forbidden_commands.is_empty() {
+            prompt.push_str(&format!(
+                "- Never suggest these commands: {}\n",
+                self.forbidden_commands.join(", ")
+            ));
+        }
+        prompt
+    }
+
+    /// Return the first forbidden command used by `command`. Programs are
+    /// compared by name, so `/usr/bin/docker` matches `docker`, and the other
+    /// words may appear anywhere in the same simple command, short flags in any
+    /// order or grouping, so `rm -fr` and `rm -r -f` match `rm -rf`. This is a
+    /// best-effort check, a command can still hide a program behind a variable
+    /// or an alias
+    pub fn forbidden_in(&self, command: &str) -> Option<&str> {
+        let commands: Vec<Vec<&str>> = command
+            .split(|c: char| "\n;|&()`".contains(c))
+            .map(|part| {
+                part.split(|c: char| c.is_whitespace() || "<>".contains(c))
+                    .filter(|word| !word.is_empty())
+                    .collect()
+            })
+            .collect();
+        self.forbidden_commands
+            .iter()
+            .find(|forbidden| {
+                let mut forbidden = forbidden.split_whitespace();
+                let Some(program) = forbidden.next() else {
+                    return false;
+                };
+                let args: Vec<&str> = forbidden.collect();
+                commands.iter().any(|words| {
+                    words.iter().enumerate().any(|(i, word)| {
+                        program_name(word) == program_name(program)
+                            && uses_args(&words[i + 1..], &args)
+                    })
+                })
+            })
+            .map(|forbidden| forbidden.as_str())
+    }
+}
+
+/// File name of a program given by path, without surrounding quotes
+fn program_name(word: &str) -> &str {
+    let word = word.trim_matches(['"', '\'']);
+    word.rsplit(['/', '\\']).next().unwrap_or(word)
+}
+
+/// Whether `words` contain all of `args`, short flags being compared letter by letter
+fn uses_args(words: &[&str], args: &[&str]) -> bool {
+    let short_flags: String = words
+        .iter()
+        .filter(|word| is_short_flags(word))
+        .flat_map(|word| word[1..].chars())
+        .collect();
+    args.iter().all(|arg| {
+        if is_short_flags(arg) {
+            arg[1..].chars().all(|flag| short_flags.contains(flag))
+        } else {
+            words.contains(arg)
+        }
+    })
+}
+
+/// `-x` or a group of short flags like `-rf`
+fn is_short_flags(word: &str) -> bool {
+    word.len() > 1 && word.starts_with('-') && !word.starts_with("--")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn forbidding(commands: &[&str]) -> ProjectInstructions {
+        ProjectInstructions {
+            forbidden_commands: commands.iter().map(|c| c.to_string()).collect(),
+            ..ProjectInstructions::default()
+        }
+    }
+
+    #[test]
+    fn matches_programs_by_name() {
+        let project = forbidding(&["docker"]);
+        assert_eq!(project.forbidden_in("docker ps"), Some("docker"));
+        assert_eq!(project.forbidden_in("/usr/bin/docker ps"), Some("docker"));
+        assert_eq!(project.forbidden_in("sudo docker ps"), Some("docker"));
+        assert_eq!(project.forbidden_in("ls && (docker ps)"), Some("docker"));
+        assert_eq!(project.forbidden_in("podman ps"), None);
+        assert_eq!(project.forbidden_in("ls dockerfiles"), None);
+    }
+
+    #[test]
+    fn splits_on_redirections() {
+        let project = forbidding(&["docker"]);
+        assert_eq!(project.forbidden_in("docker>out"), Some("docker"));
+        assert_eq!(project.forbidden_in("cat<docker"), Some("docker"));
+    }
+
+    #[test]
+    fn matches_short_flags_in_any_order() {
+        let project = forbidding(&["rm -rf"]);
+        assert_eq!(project.forbidden_in("rm -rf /tmp"), Some("rm -rf"));
+        assert_eq!(project.forbidden_in("rm -fr /tmp"), Some("rm -rf"));
+        assert_eq!(project.forbidden_in("rm -r -f x"), Some("rm -rf"));
+        assert_eq!(project.forbidden_in("rm -r x"), None);
+        assert_eq!(project.forbidden_in("rm -r x; ls -f"), None);
+    }
+
+    #[test]
+    fn matches_other_words_in_the_same_command() {
+        let project = forbidding(&["git push --force"]);
+        assert_eq!(
+            project.forbidden_in("git push origin main --force"),
+            Some("git push --force")
+        );
+        assert_eq!(project.forbidden_in("git push origin main"), None);
+    }
+}
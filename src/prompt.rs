@@ -3,6 +3,7 @@ You are a friendly command-line interface expert that can both convert natural l
 
 CONTEXT:
 - Operating System: {OS}
+{ENVIRONMENT}
 
 BEHAVIOR:
 1. If the request requires a command execution, provide the command response
@@ -11,9 +12,24 @@ BEHAVIOR:
 
 COMMAND REQUIREMENTS:
 1. Convert the natural language request into an appropriate shell command
-2. Use OS-specific syntax and commands
+2. Use OS-specific syntax and commands, and only the package managers and tools listed in CONTEXT
 3. Ensure command is executable and complete
-4. Return only raw JSON response without any markdown formatting
+4. When several commands fit the request with real trade-offs (e.g. find vs fd, sed vs perl), put the best one in "command" and up to three others in "alternatives", each with a short description of its trade-off; otherwise leave "alternatives" empty
+5. Return only raw JSON response without any markdown formatting
+"#;
+
+pub const TOOLS_PROMPT: &str = r#"
+TOOLS:
+1. Before proposing a command, call the available read-only tools whenever the answer depends on the state of this machine (files, installed programs or packages, git repositories, environment variables)
+2. Never guess paths, program availability or package names that a tool can verify
+
+RESPONSE FORMAT:
+Reply with a single JSON object with these fields:
+- "message": clear, concise message with relevant emoji
+- "command": shell command to execute, empty if no action needed
+- "dangerous": true if the command could be potentially harmful
+- "category": one of "system", "file", "network", "package", "text", "process", "other"
+- "alternatives": other commands with real trade-offs, each an object with "command", "description" and "dangerous"; usually empty
 "#;
 
 pub const EXPLANATION_PROMPT: &str = r#"
@@ -21,5 +37,50 @@ The following command failed:
 {COMMAND}
 with this error message:
 {ERROR}
-Briefly explain the cause of the failure and suggest one or two concise solutions. Do not use markdown formatting or code blocks. Keep your explanation and suggestions short and clear.
+Briefly explain the cause of the failure as "cause" and give one or two concise solutions as "suggestions". Do not use markdown formatting or code blocks. Keep your explanation and suggestions short and clear.
+"#;
+
+pub const FIX_PROMPT: &str = r#"
+The command you suggested failed.
+Command: {COMMAND}
+Exit code: {EXIT_CODE}
+Error output:
+{STDERR}
+Propose a corrected command that still fulfills my previous request. If the failure cannot be fixed by a different command, return an empty command and explain why in the message.
+"#;
+
+pub const REPAIR_PROMPT: &str = r#"
+Your previous reply could not be parsed: {ERROR}
+Reply again with only a single JSON object with the fields "message", "command", "dangerous" and "category", without any surrounding text or markdown.
+"#;
+
+pub const ADAPT_PROMPT: &str = r#"
+This request is a step of a recorded runbook. When it was recorded on {RECORDED_OS} in {RECORDED_CWD}, it was fulfilled by:
+{COMMAND}
+Adapt this command to the current operating system and directory if needed, otherwise return it unchanged.
+"#;
+
+pub const SUMMARY_PROMPT: &str = r#"
+Summarize the following conversation between a user and a shell assistant so it can replace the original messages as memory.
+Keep the user's goals, decisions, important paths, file names, commands that were run and whether they succeeded, and any open problems.
+Drop greetings and raw command output details that no longer matter. Do not use markdown formatting. Keep it under 200 words.
+
+{TRANSCRIPT}
+"#;
+
+pub const OUTPUT_SUMMARY_PROMPT: &str = r#"
+Summarize the output of this shell command so it can stand in for the full output in a conversation.
+Command: {COMMAND}
+Output:
+{OUTPUT}
+Give a short "summary" and the key facts as "highlights": errors and warnings, counts, notable values, paths and names. Do not use markdown formatting.
+"#;
+
+/// Prefix of the message that holds the summary of compacted turns
+pub const MEMORY_PREFIX: &str = "Memory of the earlier conversation:";
+
+pub const PIPED_INPUT_PROMPT: &str = r#"
+The following input was piped to me ({LINES} lines). Any command you propose receives it on its standard input, so answer questions about it directly or propose a command that reads it from stdin instead of from a file.
+Input:
+{INPUT}
 "#;
@@ -0,0 +1,122 @@
+/// Which model a request is sent to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Route {
+    /// Quick, cheap model for simple requests, explanations and summaries
+    Fast,
+    /// Most capable model for multi-step tasks and retries after a failure
+    Strong,
+    /// A model named explicitly with `@<model>`
+    Model(String),
+}
+
+/// Words hinting that a request needs several steps or careful planning
+const COMPLEX_HINTS: &[&str] = &[
+    " and then ",
+    " then ",
+    " after that",
+    " afterwards",
+    " step by step",
+    " steps",
+    " script",
+    " pipeline",
+    " migrate",
+    " set up ",
+    " setup ",
+    " configure",
+    " refactor",
+    " automate",
+    " for each ",
+    " every ",
+];
+
+/// Requests longer than this many words are treated as complex
+const COMPLEX_WORDS: usize = 25;
+
+/// Split an inline override such as `@pro find large files` into the request
+/// without it and the route it asks for. `@fast`/`@flash` and `@strong`/`@pro`
+/// pick a tier, `@gemini-...` names a model
+pub fn parse_override(input: &str) -> (String, Option<Route>) {
+    let mut route = None;
+    let mut words = Vec::new();
+    for word in input.split_whitespace() {
+        match word.strip_prefix('@') {
+            Some(name) if route.is_none() && !name.is_empty() => {
+                route = match name.to_lowercase().as_str() {
+                    "fast" | "flash" | "lite" => Some(Route::Fast),
+                    "strong" | "pro" => Some(Route::Strong),
+                    _ if name.starts_with("gemini") => Some(Route::Model(name.to_string())),
+                    // Not an override, e.g. `@echo off`
+                    _ => {
+                        words.push(word);
+                        None
+                    }
+                };
+            }
+            _ => words.push(word),
+        }
+    }
+    if route.is_none() {
+        return (input.to_string(), None);
+    }
+    (words.join(" "), route)
+}
+
+/// Route a request by how complex it looks: long requests and requests
+/// describing several steps go to the strong model
+pub fn classify(request: &str) -> Route {
+    let text = format!(" {} ", request.to_lowercase());
+    let sentences = request
+        .split(['.', ';', '\n'])
+        .filter(|s| !s.trim().is_empty())
+        .count();
+    if request.split_whitespace().count() > COMPLEX_WORDS
+        || sentences > 2
+        || COMPLEX_HINTS.iter().any(|hint| text.contains(hint))
+    {
+        Route::Strong
+    } else {
+        Route::Fast
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_overrides() {
+        assert_eq!(
+            parse_override("@pro find large files"),
+            ("find large files".to_string(), Some(Route::Strong))
+        );
+        assert_eq!(
+            parse_override("list files @flash"),
+            ("list files".to_string(), Some(Route::Fast))
+        );
+        assert_eq!(
+            parse_override("@gemini-2.5-pro list files"),
+            (
+                "list files".to_string(),
+                Some(Route::Model("gemini-2.5-pro".to_string()))
+            )
+        );
+    }
+
+    #[test]
+    fn keeps_words_that_are_not_overrides() {
+        assert_eq!(
+            parse_override("write a batch file with @echo off"),
+            ("write a batch file with @echo off".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn classifies_by_complexity() {
+        assert_eq!(classify("list files"), Route::Fast);
+        assert_eq!(
+            classify("build the project and then deploy it"),
+            Route::Strong
+        );
+        assert_eq!(classify(&"word ".repeat(COMPLEX_WORDS + 1)), Route::Strong);
+    }
+}
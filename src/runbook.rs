@@ -0,0 +1,95 @@
+use crate::session::validate_name;
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// A recorded sequence of requests and the commands that fulfilled them
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Runbook {
+    pub name: String,
+    pub created_at: u64,
+    /// OS and directory the runbook was recorded in, used when adapting steps
+    pub os: String,
+    pub cwd: String,
+    pub steps: Vec<RunbookStep>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunbookStep {
+    pub request: String,
+    pub command: String,
+    pub explanation: String,
+    pub dangerous: bool,
+}
+
+impl Runbook {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            created_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            os: std::env::consts::OS.to_string(),
+            cwd: std::env::current_dir()
+                .map(|dir| dir.display().to_string())
+                .unwrap_or_default(),
+            steps: Vec::new(),
+        }
+    }
+}
+
+/// Runbooks stored as one JSON file each in `config_dir/runbooks`
+pub struct RunbookStore {
+    dir: PathBuf,
+}
+
+impl RunbookStore {
+    pub fn open(config_dir: &Path) -> Result<Self, Box<dyn Error>> {
+        let dir = config_dir.join("runbooks");
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", name))
+    }
+
+    pub fn exists(&self, name: &str) -> bool {
+        self.path(name).exists()
+    }
+
+    pub fn names(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut names: Vec<String> = fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().and_then(|e| e.to_str()) == Some("json"))
+            .filter_map(|path| {
+                path.file_stem()
+                    .and_then(|s| s.to_str())
+                    .map(|s| s.to_string())
+            })
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    pub fn load(&self, name: &str) -> Result<Runbook, Box<dyn Error>> {
+        validate_name(name)?;
+        let path = self.path(name);
+        if !path.exists() {
+            return Err(format!("Runbook '{}' does not exist", name).into());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, runbook: &Runbook) -> Result<PathBuf, Box<dyn Error>> {
+        validate_name(&runbook.name)?;
+        let path = self.path(&runbook.name);
+        fs::write(&path, serde_json::to_string_pretty(runbook)?)?;
+        Ok(path)
+    }
+}
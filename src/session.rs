@@ -0,0 +1,239 @@
+use crate::{
+    prompt::MEMORY_PREFIX,
+    types::{Message, MessageKind, CONTEXT_VERSION},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+pub const DEFAULT_SESSION: &str = "default";
+
+#[derive(Serialize)]
+struct StoredSessionRef<'a> {
+    version: u32,
+    messages: &'a [Message],
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredSession {
+    Versioned {
+        version: u32,
+        messages: Vec<Message>,
+    },
+    /// Bare array of role/content messages written before the schema was versioned
+    Legacy(Vec<LegacyMessage>),
+}
+
+#[derive(Deserialize)]
+struct LegacyMessage {
+    role: String,
+    content: String,
+    timestamp: u64,
+}
+
+impl From<LegacyMessage> for Message {
+    fn from(legacy: LegacyMessage) -> Self {
+        let content = legacy.content;
+        let kind = if legacy.role == "user" {
+            MessageKind::UserRequest { text: content }
+        } else if let Some(stdout) = content.strip_prefix("Command output:\n") {
+            MessageKind::ExecutionResult {
+                command: String::new(),
+                exit_code: None,
+                stdout: stdout.to_string(),
+                stderr: String::new(),
+            }
+        } else if let Some((command, message)) = content
+            .strip_prefix("Command:")
+            .and_then(|rest| rest.split_once(", message:"))
+        {
+            MessageKind::ProposedCommand {
+                command: command.to_string(),
+                message: message.to_string(),
+                dangerous: false,
+                category: "other".to_string(),
+            }
+        } else if content.starts_with(MEMORY_PREFIX) {
+            MessageKind::SystemNote { text: content }
+        } else {
+            MessageKind::AssistantResponse { text: content }
+        };
+        Message {
+            kind,
+            timestamp: legacy.timestamp,
+        }
+    }
+}
+
+/// Summary of a stored session
+#[derive(Debug)]
+pub struct SessionInfo {
+    pub name: String,
+    pub messages: usize,
+    pub modified: Option<SystemTime>,
+}
+
+/// Named conversation contexts stored as one JSON file per session
+pub struct SessionStore {
+    dir: PathBuf,
+}
+
+impl SessionStore {
+    /// Open the store in `config_dir/sessions`, migrating the legacy single
+    /// `nexsh_context.json` into the default session
+    pub fn open(config_dir: &Path) -> Result<Self, Box<dyn Error>> {
+        let store = Self {
+            dir: config_dir.join("sessions"),
+        };
+        fs::create_dir_all(&store.dir)?;
+
+        let legacy_context = config_dir.join("nexsh_context.json");
+        if legacy_context.exists() && !store.exists(DEFAULT_SESSION) {
+            fs::rename(&legacy_context, store.path(DEFAULT_SESSION))?;
+        }
+        Ok(store)
+    }
+
+    /// Path of a session file; callers validate `name` first so it can't point
+    /// outside the sessions directory
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", name))
+    }
+
+    pub fn exists(&self, name: &str) -> bool {
+        validate_name(name).is_ok() && self.path(name).exists()
+    }
+
+    pub fn list(&self) -> Result<Vec<SessionInfo>, Box<dyn Error>> {
+        let mut sessions = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            sessions.push(SessionInfo {
+                name: name.to_string(),
+                messages: self.load(name).map(|m| m.len()).unwrap_or(0),
+                modified: fs::metadata(&path).and_then(|m| m.modified()).ok(),
+            });
+        }
+        sessions.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(sessions)
+    }
+
+    /// Load the messages of a session, empty if it doesn't exist yet
+    pub fn load(&self, name: &str) -> Result<Vec<Message>, Box<dyn Error>> {
+        validate_name(name)?;
+        let path = self.path(name);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let content = fs::read_to_string(path)?;
+        parse_messages(&content).map_err(|e| format!("Session '{}': {}", name, e).into())
+    }
+
+    pub fn save(&self, name: &str, messages: &[Message]) -> Result<(), Box<dyn Error>> {
+        validate_name(name)?;
+        let content = serde_json::to_string_pretty(&StoredSessionRef {
+            version: CONTEXT_VERSION,
+            messages,
+        })?;
+        fs::write(self.path(name), content)?;
+        Ok(())
+    }
+
+    pub fn delete(&self, name: &str) -> Result<(), Box<dyn Error>> {
+        validate_name(name)?;
+        if !self.exists(name) {
+            return Err(format!("Session '{}' does not exist", name).into());
+        }
+        fs::remove_file(self.path(name))?;
+        Ok(())
+    }
+
+    pub fn rename(&self, from: &str, to: &str) -> Result<(), Box<dyn Error>> {
+        validate_name(from)?;
+        validate_name(to)?;
+        if !self.exists(from) {
+            return Err(format!("Session '{}' does not exist", from).into());
+        }
+        if self.exists(to) {
+            return Err(format!("Session '{}' already exists", to).into());
+        }
+        fs::rename(self.path(from), self.path(to))?;
+        Ok(())
+    }
+}
+
+/// Parse stored messages, migrating the legacy unversioned format
+pub fn parse_messages(content: &str) -> Result<Vec<Message>, Box<dyn Error>> {
+    match serde_json::from_str(content)? {
+        StoredSession::Versioned { version, messages } => {
+            if version > CONTEXT_VERSION {
+                return Err(format!("saved by a newer NexSh (schema version {})", version).into());
+            }
+            Ok(messages)
+        }
+        StoredSession::Legacy(messages) => Ok(messages.into_iter().map(Message::from).collect()),
+    }
+}
+
+/// Session and runbook names double as file names, so only allow a safe character set
+pub fn validate_name(name: &str) -> Result<(), Box<dyn Error>> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c));
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "Invalid name '{}': use letters, digits, '-', '_' or '.'",
+            name
+        )
+        .into())
+    }
+}
+
+/// Session name for a directory: the name of its git repository root, or of
+/// the directory itself outside a repository
+pub fn name_for_dir(dir: &Path) -> String {
+    let root = dir
+        .ancestors()
+        .find(|ancestor| ancestor.join(".git").exists())
+        .unwrap_or(dir);
+    sanitize_name(
+        &root
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    )
+}
+
+/// Turn arbitrary text into a valid session name
+pub fn sanitize_name(text: &str) -> String {
+    let name: String = text
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "-_".contains(c) {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    if name.is_empty() {
+        DEFAULT_SESSION.to_string()
+    } else {
+        name
+    }
+}
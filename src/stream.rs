@@ -0,0 +1,151 @@
+use colored::*;
+use std::io::{self, Write};
+
+/// Renders a JSON reply while it is still being received: the `message` is
+/// printed as it grows and the `command` as soon as its value is complete
+#[derive(Default)]
+pub struct MessageStream {
+    text: String,
+    message_chars: usize,
+    line_open: bool,
+    pub message_shown: bool,
+    pub command_shown: bool,
+}
+
+impl MessageStream {
+    /// Feed the next piece of reply text
+    pub fn push(&mut self, delta: &str) {
+        self.text.push_str(delta);
+
+        if let Some((message, _)) = partial_string(&self.text, "message") {
+            let new: String = message.chars().skip(self.message_chars).collect();
+            if !new.is_empty() && !self.command_shown {
+                if !self.message_shown {
+                    print!("{} ", "🤖 →".green());
+                    self.message_shown = true;
+                    self.line_open = true;
+                }
+                print!("{}", new.yellow());
+                let _ = io::stdout().flush();
+                self.message_chars += new.chars().count();
+            }
+        }
+
+        if !self.command_shown {
+            if let Some((command, true)) = partial_string(&self.text, "command") {
+                if !command.trim().is_empty() {
+                    self.finish();
+                    println!("{} {}", "→".blue(), command.trim());
+                    self.command_shown = true;
+                }
+            }
+        }
+    }
+
+    /// End the line of a message that is still being printed
+    pub fn finish(&mut self) {
+        if self.line_open {
+            println!();
+            self.line_open = false;
+        }
+    }
+
+    /// Start over for a new reply, e.g. after asking the model to correct one
+    pub fn restart(&mut self) {
+        self.finish();
+        *self = Self::default();
+    }
+}
+
+/// The value received so far of the string field `key` in a partial JSON
+/// object, and whether the closing quote has arrived
+fn partial_string(json: &str, key: &str) -> Option<(String, bool)> {
+    let pattern = format!("\"{}\"", key);
+    let rest = &json[json.find(&pattern)? + pattern.len()..];
+    let rest = rest.trim_start().strip_prefix(':')?.trim_start();
+    let mut chars = rest.strip_prefix('"')?.chars();
+
+    let mut value = String::new();
+    let mut units: Vec<u16> = Vec::new();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            if c == '"' {
+                return Some((value, true));
+            }
+            value.push(c);
+            continue;
+        }
+        // Stop before an escape sequence that is still incomplete
+        let Some(escaped) = chars.next() else {
+            break;
+        };
+        match escaped {
+            'n' => value.push('\n'),
+            't' => value.push('\t'),
+            'r' => value.push('\r'),
+            'b' => value.push('\u{8}'),
+            'f' => value.push('\u{c}'),
+            'u' => {
+                let hex: String = chars.by_ref().take(4).collect();
+                let Ok(unit) = u16::from_str_radix(&hex, 16) else {
+                    break;
+                };
+                if hex.len() < 4 {
+                    break;
+                }
+                units.push(unit);
+                // Surrogate pairs arrive as two escapes, wait for the second one
+                if (0xD800..0xDC00).contains(&unit) {
+                    continue;
+                }
+                value.extend(char::decode_utf16(units.drain(..)).map(|c| c.unwrap_or('\u{FFFD}')));
+            }
+            other => value.push(other),
+        }
+    }
+    Some((value, false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_complete_and_partial_strings() {
+        let json = r#"{"message": "Listing files", "command": "ls -"#;
+        assert_eq!(
+            partial_string(json, "message"),
+            Some(("Listing files".to_string(), true))
+        );
+        assert_eq!(
+            partial_string(json, "command"),
+            Some(("ls -".to_string(), false))
+        );
+        assert_eq!(partial_string(json, "category"), None);
+    }
+
+    #[test]
+    fn decodes_escapes() {
+        let json = r#"{"message": "a\n\"b\" é 📂"}"#;
+        assert_eq!(
+            partial_string(json, "message"),
+            Some(("a\n\"b\" é 📂".to_string(), true))
+        );
+    }
+
+    #[test]
+    fn stops_before_incomplete_escapes() {
+        assert_eq!(
+            partial_string(r#"{"message": "a\"#, "message"),
+            Some(("a".to_string(), false))
+        );
+        assert_eq!(
+            partial_string(r#"{"message": "a\u00"#, "message"),
+            Some(("a".to_string(), false))
+        );
+        assert_eq!(
+            partial_string(r#"{"message": "a\ud83d"#, "message"),
+            Some(("a".to_string(), false))
+        );
+    }
+}
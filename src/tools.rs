@@ -0,0 +1,349 @@
+use crate::redact;
+use serde_json::{json, Value};
+use std::{
+    env, fs,
+    io::Read,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+const MAX_DIR_ENTRIES: usize = 200;
+const DEFAULT_FILE_LINES: usize = 40;
+const MAX_FILE_LINES: usize = 200;
+const MAX_FILE_BYTES: u64 = 16 * 1024;
+const MAX_OUTPUT_BYTES: usize = 8 * 1024;
+
+/// File names that are never read, so secrets don't leave the machine
+const SECRET_FILES: &[&str] = &[
+    "id_rsa",
+    "id_dsa",
+    "id_ecdsa",
+    "id_ed25519",
+    ".netrc",
+    ".pgpass",
+    ".git-credentials",
+    ".npmrc",
+    ".pypirc",
+    "credentials",
+    "nexsh_config.json",
+];
+const SECRET_EXTENSIONS: &[&str] = &["pem", "key", "p12", "pfx", "kdbx"];
+/// Directories holding credentials, matched against consecutive path components
+const SECRET_DIRS: &[&str] = &[
+    ".ssh",
+    ".gnupg",
+    ".aws",
+    ".azure",
+    ".kube",
+    ".docker",
+    ".config/gh",
+    ".config/gcloud",
+];
+
+/// Package managers queried by `check_package`, with the arguments preceding the package name,
+/// ending with `--` for those that accept it so the name is never taken as an option
+const PACKAGE_QUERIES: &[(&str, &[&str])] = &[
+    ("dpkg-query", &["-W", "-f=${Version}", "--"]),
+    ("rpm", &["-q", "--qf", "%{VERSION}-%{RELEASE}", "--"]),
+    ("pacman", &["-Q", "--"]),
+    ("apk", &["info", "-e", "--"]),
+    ("brew", &["list", "--versions"]),
+];
+
+/// Gemini function declarations for the read-only inspection tools
+pub fn declarations() -> Value {
+    json!([{
+        "function_declarations": [
+            {
+                "name": "list_directory",
+                "description": "List the entries of a directory. Directories end with '/'.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "path": {
+                            "type": "string",
+                            "description": "Directory to list, defaults to the current directory"
+                        }
+                    }
+                }
+            },
+            {
+                "name": "read_file_head",
+                "description": "Read the first lines of a text file in the current directory.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "path": {
+                            "type": "string",
+                            "description": "File to read"
+                        },
+                        "lines": {
+                            "type": "integer",
+                            "description": "Number of lines to read, at most 200 (default 40)"
+                        }
+                    },
+                    "required": ["path"]
+                }
+            },
+            {
+                "name": "which",
+                "description": "Find the full path of an executable available in PATH.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "program": {
+                            "type": "string",
+                            "description": "Executable name, e.g. docker"
+                        }
+                    },
+                    "required": ["program"]
+                }
+            },
+            {
+                "name": "check_package",
+                "description": "Check whether a package is installed with the system package managers.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "package": {
+                            "type": "string",
+                            "description": "Package name, e.g. openssl"
+                        }
+                    },
+                    "required": ["package"]
+                }
+            },
+            {
+                "name": "git_status",
+                "description": "Show the branch and short status of a git repository.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "path": {
+                            "type": "string",
+                            "description": "Repository directory, defaults to the current directory"
+                        }
+                    }
+                }
+            },
+            {
+                "name": "env_var_names",
+                "description": "List the names (never the values) of the environment variables.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "prefix": {
+                            "type": "string",
+                            "description": "Only return names starting with this prefix"
+                        }
+                    }
+                }
+            }
+        ]
+    }])
+}
+
+/// Run a tool requested by the model, reporting failures back to it as `{"error": ...}`.
+/// Files outside the current directory are only read with `read_anywhere`
+pub fn call(name: &str, args: &Value, read_anywhere: bool) -> Value {
+    let str_arg = |key: &str| args.get(key).and_then(|v| v.as_str());
+    let result = match name {
+        "list_directory" => list_directory(str_arg("path").unwrap_or(".")),
+        "read_file_head" => match str_arg("path") {
+            Some(path) => read_file_head(
+                path,
+                args.get("lines")
+                    .and_then(|v| v.as_u64())
+                    .map_or(DEFAULT_FILE_LINES, |n| n as usize),
+                read_anywhere,
+            ),
+            None => Err("Missing 'path' argument".to_string()),
+        },
+        "which" => match str_arg("program") {
+            Some(program) => Ok(json!({
+                "program": program,
+                "path": find_in_path(program).map(|p| p.display().to_string()),
+            })),
+            None => Err("Missing 'program' argument".to_string()),
+        },
+        "check_package" => match str_arg("package") {
+            Some(package) => check_package(package),
+            None => Err("Missing 'package' argument".to_string()),
+        },
+        "git_status" => git_status(str_arg("path").unwrap_or(".")),
+        "env_var_names" => Ok(env_var_names(str_arg("prefix").unwrap_or(""))),
+        _ => Err(format!("Unknown tool: {}", name)),
+    };
+    result.unwrap_or_else(|e| json!({ "error": e }))
+}
+
+/// Locate an executable in `PATH`, honoring `PATHEXT` on Windows
+pub fn find_in_path(program: &str) -> Option<PathBuf> {
+    let extensions: Vec<String> = if cfg!(windows) {
+        env::var("PATHEXT")
+            .unwrap_or_else(|_| ".EXE;.CMD;.BAT".to_string())
+            .split(';')
+            .map(|ext| ext.to_string())
+            .chain(std::iter::once(String::new()))
+            .collect()
+    } else {
+        vec![String::new()]
+    };
+    env::split_paths(&env::var_os("PATH")?).find_map(|dir| {
+        extensions.iter().find_map(|ext| {
+            let candidate = dir.join(format!("{}{}", program, ext));
+            candidate.is_file().then_some(candidate)
+        })
+    })
+}
+
+/// Run a program and return whether it succeeded along with its capped stdout
+pub fn run_capped(program: &str, args: &[&str]) -> Option<(bool, String)> {
+    let output = Command::new(program).args(args).output().ok()?;
+    let mut stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    truncate_to(&mut stdout, MAX_OUTPUT_BYTES);
+    Some((output.status.success(), stdout))
+}
+
+fn truncate_to(text: &mut String, max_bytes: usize) {
+    if text.len() > max_bytes {
+        let mut end = max_bytes;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        text.push_str("\n…(truncated)");
+    }
+}
+
+fn list_directory(path: &str) -> Result<Value, String> {
+    let mut entries: Vec<String> = fs::read_dir(path)
+        .map_err(|e| e.to_string())?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                format!("{}/", name)
+            } else {
+                name
+            }
+        })
+        .collect();
+    entries.sort();
+    let total = entries.len();
+    entries.truncate(MAX_DIR_ENTRIES);
+    Ok(json!({
+        "path": path,
+        "entries": entries,
+        "total": total,
+    }))
+}
+
+fn is_secret_path(path: &Path) -> bool {
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    file_name == ".env"
+        || file_name.starts_with(".env.")
+        || SECRET_FILES.contains(&file_name.as_str())
+        || SECRET_EXTENSIONS.contains(&extension.as_str())
+        || {
+            let path = format!("/{}", path.to_string_lossy().replace('\\', "/"));
+            SECRET_DIRS
+                .iter()
+                .any(|dir| path.contains(&format!("/{}/", dir)))
+        }
+}
+
+fn read_file_head(path: &str, lines: usize, read_anywhere: bool) -> Result<Value, String> {
+    let path = Path::new(path);
+    // Check the file actually read, not a symlink or `..` pointing to it
+    let resolved = fs::canonicalize(path).map_err(|e| e.to_string())?;
+    if !resolved.is_file() {
+        return Err("Not a regular file".to_string());
+    }
+    if is_secret_path(path) || is_secret_path(&resolved) {
+        return Err("Refusing to read a file that may contain secrets".to_string());
+    }
+    if !read_anywhere {
+        let cwd = env::current_dir()
+            .and_then(fs::canonicalize)
+            .map_err(|e| e.to_string())?;
+        if !resolved.starts_with(&cwd) {
+            return Err(
+                "Refusing to read a file outside the current directory (see tools_read_anywhere)"
+                    .to_string(),
+            );
+        }
+    }
+    let mut buffer = Vec::new();
+    fs::File::open(&resolved)
+        .and_then(|file| file.take(MAX_FILE_BYTES).read_to_end(&mut buffer))
+        .map_err(|e| e.to_string())?;
+    if buffer.contains(&0) {
+        return Err("File looks binary".to_string());
+    }
+    let text = String::from_utf8_lossy(&buffer);
+    let head: Vec<&str> = text.lines().take(lines.min(MAX_FILE_LINES)).collect();
+    Ok(json!({
+        "path": path.display().to_string(),
+        "content": redact::secrets(&head.join("\n")),
+    }))
+}
+
+/// Whether `package` looks like a package name, so the model can't pass options
+/// such as `--eval` or `--pipe` to the package manager
+fn is_package_name(package: &str) -> bool {
+    !package.is_empty()
+        && !package.starts_with('-')
+        && package
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "._+:-".contains(c))
+}
+
+fn check_package(package: &str) -> Result<Value, String> {
+    if !is_package_name(package) {
+        return Err(format!("Invalid package name: {}", package));
+    }
+    let results: Vec<Value> = PACKAGE_QUERIES
+        .iter()
+        .filter(|(manager, _)| find_in_path(manager).is_some())
+        .filter_map(|(manager, args)| {
+            let mut args = args.to_vec();
+            args.push(package);
+            let (installed, version) = run_capped(manager, &args)?;
+            Some(json!({
+                "manager": manager,
+                "installed": installed,
+                "version": installed.then(|| version.trim().to_string()),
+            }))
+        })
+        .collect();
+    Ok(json!({
+        "package": package,
+        "results": results,
+        "executable": find_in_path(package).map(|p| p.display().to_string()),
+    }))
+}
+
+fn git_status(path: &str) -> Result<Value, String> {
+    match run_capped("git", &["-C", path, "status", "--short", "--branch"]) {
+        Some((true, status)) => Ok(json!({ "path": path, "status": status })),
+        Some((false, _)) => Err(format!("{} is not a git repository", path)),
+        None => Err("git is not installed".to_string()),
+    }
+}
+
+fn env_var_names(prefix: &str) -> Value {
+    let mut names: Vec<String> = env::vars_os()
+        .map(|(name, _)| name.to_string_lossy().into_owned())
+        .filter(|name| name.starts_with(prefix))
+        .collect();
+    names.sort();
+    json!(names)
+}
@@ -1,24 +1,387 @@
+use crate::usage::ModelPrice;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Version of the stored conversation schema
+pub const CONTEXT_VERSION: u32 = 2;
+
+/// One entry of the conversation context
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
-    pub role: String,
-    pub content: String,
+    #[serde(flatten)]
+    pub kind: MessageKind,
     pub timestamp: u64,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MessageKind {
+    /// Natural-language request typed by the user
+    UserRequest { text: String },
+    /// Model reply that doesn't propose a command
+    AssistantResponse { text: String },
+    /// Command suggested by the model
+    ProposedCommand {
+        command: String,
+        message: String,
+        dangerous: bool,
+        category: String,
+    },
+    /// Outcome of running a command, with possibly truncated or summarized output
+    ExecutionResult {
+        command: String,
+        exit_code: Option<i32>,
+        stdout: String,
+        stderr: String,
+    },
+    /// Note added by NexSh itself, such as summaries or fix requests
+    SystemNote { text: String },
+}
+
+impl Message {
+    pub fn new(kind: MessageKind) -> Self {
+        Self {
+            kind,
+            timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+        }
+    }
+
+    /// Gemini role: only what the model actually said is attributed to it
+    pub fn gemini_role(&self) -> &'static str {
+        match self.kind {
+            MessageKind::AssistantResponse { .. } | MessageKind::ProposedCommand { .. } => "model",
+            MessageKind::UserRequest { .. }
+            | MessageKind::ExecutionResult { .. }
+            | MessageKind::SystemNote { .. } => "user",
+        }
+    }
+
+    /// Text sent to the model for this message
+    pub fn text(&self) -> String {
+        match &self.kind {
+            MessageKind::UserRequest { text } | MessageKind::AssistantResponse { text } => {
+                text.clone()
+            }
+            MessageKind::ProposedCommand {
+                command,
+                message,
+                dangerous,
+                category,
+            } => serde_json::json!({
+                "message": message,
+                "command": command,
+                "dangerous": dangerous,
+                "category": category,
+            })
+            .to_string(),
+            MessageKind::ExecutionResult {
+                command,
+                exit_code,
+                stdout,
+                stderr,
+            } => {
+                let mut text = String::from("[Execution result]");
+                if !command.is_empty() {
+                    text.push_str(&format!(" `{}`", command));
+                }
+                if let Some(code) = exit_code {
+                    text.push_str(&format!(" exited with code {}", code));
+                }
+                if !stdout.is_empty() {
+                    text.push_str(&format!("\nstdout:\n{}", stdout));
+                }
+                if !stderr.is_empty() {
+                    text.push_str(&format!("\nstderr:\n{}", stderr));
+                }
+                text
+            }
+            MessageKind::SystemNote { text } => format!("[NexSh note] {}", text),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct NexShConfig {
     pub api_key: String,
     pub history_size: usize,
     pub max_context_messages: usize,
+    /// Estimated tokens of conversation sent with each request before older turns are summarized
+    pub context_token_budget: usize,
+    /// Lines kept from the start of a large command output
+    pub output_head_lines: usize,
+    /// Lines kept from the end of a large command output
+    pub output_tail_lines: usize,
+    /// Maximum bytes of a command output stored in the context
+    pub max_output_bytes: usize,
+    /// Store a model-written summary of large outputs instead of the truncated text
+    pub summarize_large_output: bool,
+    /// Maximum bytes read from piped stdin
+    pub max_stdin_bytes: usize,
     pub model: Option<String>,
+    /// Model for simple requests, failure explanations and summaries, `model` if unset
+    pub fast_model: Option<String>,
+    /// Model for multi-step requests and fixes after a failure, `model` if unset
+    pub strong_model: Option<String>,
+    /// Ask the model for a corrected command when an executed command fails
+    pub auto_fix: bool,
+    /// Maximum number of corrected commands proposed for a single failure
+    pub max_fix_attempts: usize,
+    /// Let the model call read-only tools to inspect the system before answering
+    pub enable_tools: bool,
+    /// Maximum number of tool calls per request
+    pub max_tool_calls: usize,
+    /// Let tools read files outside the current directory
+    pub tools_read_anywhere: bool,
+    /// Describe the local machine (cwd, shell, distro, tools, git) in the system prompt
+    pub environment_context: bool,
+    /// Tools whose availability is reported to the model
+    pub context_tools: Vec<String>,
+    /// Maximum number of current directory entries reported to the model
+    pub context_listing_entries: usize,
+    /// Replaces the built-in system prompt (`{OS}` and `{ENVIRONMENT}` are substituted)
+    pub system_prompt: Option<String>,
+    /// Replaces the built-in failure explanation prompt (`{COMMAND}`, `{ERROR}`)
+    pub explanation_prompt: Option<String>,
+    /// Replaces the built-in auto-fix prompt (`{COMMAND}`, `{EXIT_CODE}`, `{STDERR}`)
+    pub fix_prompt: Option<String>,
+    /// Name of the active conversation session
+    pub session: String,
+    /// Pick the session from the current git repository (or directory) name
+    pub auto_session: bool,
+    /// Reuse responses to identical requests made in the same environment
+    pub cache_enabled: bool,
+    /// Seconds a cached response stays valid
+    pub cache_ttl_secs: u64,
+    /// Maximum number of cached requests
+    pub cache_max_entries: usize,
+    /// Seconds to wait for a Gemini response before giving up
+    pub request_timeout_secs: u64,
+    /// Retries of rate-limited, timed out or failed network requests
+    pub max_retries: u32,
+    /// Render replies while they arrive instead of waiting for the full response
+    pub stream_responses: bool,
+    /// USD per million input and output tokens, by model name prefix
+    pub pricing: BTreeMap<String, ModelPrice>,
+    /// Estimated monthly spend in USD at which `budget_action` applies
+    pub monthly_budget: Option<f64>,
+    pub budget_action: BudgetAction,
 }
 
-#[derive(Debug, Deserialize)]
+/// What happens once the monthly budget is exceeded
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BudgetAction {
+    Warn,
+    Block,
+}
+
+/// Command categories the model may report; anything else is mapped to "other"
+pub const CATEGORIES: &[&str] = &[
+    "system", "file", "network", "package", "text", "process", "other",
+];
+
+/// Alternatives shown next to the proposed command, for at most four choices in total
+pub const MAX_ALTERNATIVES: usize = 3;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct GeminiResponse {
     pub message: String,
     pub command: String,
+    #[serde(default = "dangerous_by_default")]
     pub dangerous: bool,
     pub category: String,
+    /// Other commands fulfilling the request, each with its trade-off
+    pub alternatives: Vec<Alternative>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Alternative {
+    pub command: String,
+    pub description: String,
+    #[serde(default = "dangerous_by_default")]
+    pub dangerous: bool,
+}
+
+/// A reply that doesn't say whether its command is dangerous is treated as if
+/// it were, so the command is never run without confirmation
+fn dangerous_by_default() -> bool {
+    true
+}
+
+impl GeminiResponse {
+    /// Parse a model reply leniently: take the first JSON object in the text
+    /// (ignoring markdown fences or prose around it), default missing fields and
+    /// map unknown categories to "other"
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let json = first_json_object(text).ok_or("no JSON object in the reply")?;
+        let mut response: GeminiResponse = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        response.message = response.message.trim().to_string();
+        response.command = response.command.trim().to_string();
+        if response.message.is_empty() && response.command.is_empty() {
+            return Err("the reply has neither a message nor a command".to_string());
+        }
+        response.category = response.category.trim().to_lowercase();
+        if !CATEGORIES.contains(&response.category.as_str()) {
+            response.category = "other".to_string();
+        }
+        // Alternatives only make sense next to a proposed command
+        let mut seen = vec![response.command.clone()];
+        response.alternatives.retain_mut(|alternative| {
+            alternative.command = alternative.command.trim().to_string();
+            let keep = !response.command.is_empty()
+                && !alternative.command.is_empty()
+                && !seen.contains(&alternative.command);
+            seen.push(alternative.command.clone());
+            keep
+        });
+        response.alternatives.truncate(MAX_ALTERNATIVES);
+        Ok(response)
+    }
+}
+
+/// The first balanced `{...}` in `text`, skipping braces inside JSON strings
+fn first_json_object(text: &str) -> Option<&str> {
+    let start = text.find('{')?;
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in text[start..].char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&text[start..start + i + 1]);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Structured explanation of a failed command
+#[derive(Debug, Deserialize)]
+pub struct FailureAnalysis {
+    pub cause: String,
+    #[serde(default)]
+    pub suggestions: Vec<String>,
+}
+
+/// Structured summary of a large command output
+#[derive(Debug, Deserialize)]
+pub struct OutputSummary {
+    pub summary: String,
+    #[serde(default)]
+    pub highlights: Vec<String>,
+}
+
+/// Captured result of running a shell command
+#[derive(Debug)]
+pub struct CommandResult {
+    pub command: String,
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl CommandResult {
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+/// Everything `--output json` reports about one request
+#[derive(Debug, Default, Serialize)]
+pub struct CommandReport {
+    pub message: String,
+    pub command: Option<String>,
+    pub category: Option<String>,
+    /// `safe` or `dangerous`
+    pub risk: Option<String>,
+    pub alternatives: Vec<Alternative>,
+    pub model: String,
+    pub cached: bool,
+    pub executed: bool,
+    /// Why a proposed command didn't run: `dry-run`, `dangerous`, `forbidden`, or
+    /// `cached` for a cached reply without `--yes`
+    pub skipped: Option<String>,
+    pub exit_code: Option<i32>,
+    pub stdout: Option<String>,
+    pub stderr: Option<String>,
+    pub timing: Timing,
+}
+
+/// Durations in milliseconds
+#[derive(Debug, Default, Serialize)]
+pub struct Timing {
+    pub generation_ms: u64,
+    pub execution_ms: Option<u64>,
+    pub total_ms: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_fenced_json() {
+        let reply = "Here you go:\n```json\n{\"message\": \"List files\", \"command\": \"ls -la\", \"dangerous\": false, \"category\": \"file\"}\n```\n";
+        let response = GeminiResponse::parse(reply).unwrap();
+        assert_eq!(response.message, "List files");
+        assert_eq!(response.command, "ls -la");
+        assert!(!response.dangerous);
+        assert_eq!(response.category, "file");
+    }
+
+    #[test]
+    fn skips_braces_inside_strings() {
+        let reply = r#"{"message": "Print a brace }", "command": "awk '{print $1}' f", "dangerous": false, "category": "text"} {"message": "other"}"#;
+        let response = GeminiResponse::parse(reply).unwrap();
+        assert_eq!(response.message, "Print a brace }");
+        assert_eq!(response.command, "awk '{print $1}' f");
+    }
+
+    #[test]
+    fn missing_dangerous_flag_is_dangerous() {
+        let reply = r#"{"message": "Clean up", "command": "rm -r build", "category": "file",
+            "alternatives": [{"command": "cargo clean", "description": "Cargo only"}]}"#;
+        let response = GeminiResponse::parse(reply).unwrap();
+        assert!(response.dangerous);
+        assert!(response.alternatives[0].dangerous);
+    }
+
+    #[test]
+    fn unknown_category_is_other() {
+        let reply =
+            r#"{"message": "m", "command": "c", "dangerous": false, "category": " Docker "}"#;
+        assert_eq!(GeminiResponse::parse(reply).unwrap().category, "other");
+        let reply =
+            r#"{"message": "m", "command": "c", "dangerous": false, "category": "Network"}"#;
+        assert_eq!(GeminiResponse::parse(reply).unwrap().category, "network");
+    }
+
+    #[test]
+    fn rejects_empty_replies() {
+        assert!(GeminiResponse::parse("").is_err());
+        assert!(GeminiResponse::parse("Sorry, I can't help").is_err());
+        assert!(GeminiResponse::parse(r#"{"message": " ", "command": ""}"#).is_err());
+        assert!(GeminiResponse::parse(r#"{"message": "unterminated"#).is_err());
+    }
 }
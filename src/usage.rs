@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Price of a model in USD per million tokens
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+/// Published paid-tier prices of common models, used as the default pricing table
+pub fn default_pricing() -> BTreeMap<String, ModelPrice> {
+    [
+        ("gemini-2.5-pro", 1.25, 10.0),
+        ("gemini-2.5-flash", 0.30, 2.50),
+        ("gemini-2.5-flash-lite", 0.10, 0.40),
+        ("gemini-2.0-flash", 0.10, 0.40),
+        ("gemini-2.0-flash-lite", 0.075, 0.30),
+        ("gemini-1.5-pro", 1.25, 5.0),
+        ("gemini-1.5-flash", 0.075, 0.30),
+    ]
+    .into_iter()
+    .map(|(model, input, output)| {
+        (
+            model.to_string(),
+            ModelPrice {
+                input_per_million: input,
+                output_per_million: output,
+            },
+        )
+    })
+    .collect()
+}
+
+/// Price of `model`: an exact entry, or the longest entry the model name starts
+/// with so versioned names like `gemini-2.0-flash-001` are covered
+pub fn price_for(pricing: &BTreeMap<String, ModelPrice>, model: &str) -> Option<ModelPrice> {
+    pricing
+        .iter()
+        .filter(|(name, _)| model.starts_with(name.as_str()))
+        .max_by_key(|(name, _)| name.len())
+        .map(|(_, price)| *price)
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Counters {
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub response_tokens: u64,
+    /// Estimated cost in USD
+    pub cost: f64,
+}
+
+impl Counters {
+    fn add(&mut self, prompt_tokens: u64, response_tokens: u64, cost: f64) {
+        self.requests += 1;
+        self.prompt_tokens += prompt_tokens;
+        self.response_tokens += response_tokens;
+        self.cost += cost;
+    }
+}
+
+/// Usage counters kept per session, calendar month (`YYYY-MM`) and model
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Usage {
+    pub total: Counters,
+    pub sessions: BTreeMap<String, Counters>,
+    pub months: BTreeMap<String, Counters>,
+    pub models: BTreeMap<String, Counters>,
+}
+
+/// Usage counters stored as a JSON file in the config directory
+pub struct UsageStore {
+    path: PathBuf,
+}
+
+impl UsageStore {
+    pub fn open(config_dir: &Path) -> Self {
+        Self {
+            path: config_dir.join("nexsh_usage.json"),
+        }
+    }
+
+    pub fn load(&self) -> Usage {
+        fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Add one request to the counters of `session`, `month` and `model`
+    pub fn record(
+        &self,
+        session: &str,
+        month: &str,
+        model: &str,
+        prompt_tokens: u64,
+        response_tokens: u64,
+        cost: f64,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut usage = self.load();
+        usage.total.add(prompt_tokens, response_tokens, cost);
+        for counters in [
+            usage.sessions.entry(session.to_string()).or_default(),
+            usage.months.entry(month.to_string()).or_default(),
+            usage.models.entry(model.to_string()).or_default(),
+        ] {
+            counters.add(prompt_tokens, response_tokens, cost);
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(&usage)?)?;
+        Ok(())
+    }
+}
+
+/// Compact token count, e.g. 950, 12.3K, 4.1M
+pub fn format_tokens(tokens: u64) -> String {
+    match tokens {
+        0..=999 => tokens.to_string(),
+        1_000..=999_999 => format!("{:.1}K", tokens as f64 / 1_000.0),
+        _ => format!("{:.1}M", tokens as f64 / 1_000_000.0),
+    }
+}